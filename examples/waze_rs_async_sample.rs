@@ -0,0 +1,25 @@
+use waze_rs::helpers::{Region, VehicleType};
+use waze_rs::waze_route_calculator::WazeRouteCalculator;
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+
+    println!("Hello async Waze-rs!");
+
+    let mut wrc = WazeRouteCalculator::builder()
+        .set_region(Region::IL)
+        .set_vehicle_type(VehicleType::CAR)
+        .build_async();
+
+    wrc.set_address("Maale Adummim", "Tel Aviv, Israel")
+        .await
+        .expect("set_address() failed");
+
+    println!("{:?}", wrc);
+    let res = wrc
+        .calculate_route()
+        .await
+        .expect("calculate_route_info() failed");
+    println!("{:?}", res);
+}
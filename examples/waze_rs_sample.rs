@@ -1,4 +1,3 @@
-use tracing_subscriber;
 use waze_rs::helpers::{Region, VehicleType};
 use waze_rs::waze_route_calculator::WazeRouteCalculator;
 
@@ -0,0 +1,524 @@
+use crate::helpers::{parse_coordinates, DepartureTime, Endpoint, Region, VehicleType};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::route_cache::RouteCache;
+use crate::transport::{TransportRequest, TransportResponse};
+use crate::waze_route_calculator::{
+    Coordinates, MultiLegRoute, RouteRequest, RouteSummary, WazeAddress, WazeAddressAnswer,
+    WazeAddressCoordinates, WazeRoute, WazeRouteCalculator, WazeRouteCalculatorBuilder,
+    WazeRouteCalculatorError,
+};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tracing::debug;
+
+impl WazeRouteCalculatorBuilder {
+    /// Builds the `AsyncWazeRouteCalculator` instance.
+    ///
+    /// # Returns
+    ///
+    /// An `AsyncWazeRouteCalculator` instance with the configured options.
+    pub fn build_async(self) -> AsyncWazeRouteCalculator {
+        AsyncWazeRouteCalculator {
+            inner: self.build(),
+        }
+    }
+}
+
+/// A struct representing an async Waze route calculator.
+///
+/// Shares its options, region tables and response parsing with `WazeRouteCalculator`, but only
+/// exposes its non-blocking methods, the blocking ones panicking inside an async runtime.
+#[derive(Debug)]
+pub struct AsyncWazeRouteCalculator {
+    inner: WazeRouteCalculator,
+}
+
+impl AsyncWazeRouteCalculator {
+    /// Returns the region of the Waze servers.
+    pub fn region(&self) -> Region {
+        self.inner.region
+    }
+
+    /// Returns the vehicle type of the routes.
+    pub fn vehicle_type(&self) -> VehicleType {
+        self.inner.vehicle_type
+    }
+
+    /// Returns the departure time of the routes.
+    pub fn departure_time(&self) -> DepartureTime {
+        self.inner.departure_time
+    }
+
+    /// Returns the start coordinates, if they are set.
+    pub fn start_coords(&self) -> Option<Coordinates> {
+        self.inner.start_coords
+    }
+
+    /// Returns the end coordinates, if they are set.
+    pub fn end_coords(&self) -> Option<Coordinates> {
+        self.inner.end_coords
+    }
+
+    /// Returns the via points, the ordered intermediate stops between the start and the end.
+    pub fn via_coords(&self) -> &[Coordinates] {
+        &self.inner.via_coords
+    }
+
+    /// Returns the routing answer cache, to read its counters or invalidate it.
+    pub fn route_cache(&self) -> Option<&Arc<RouteCache>> {
+        self.inner.route_cache()
+    }
+
+    /// Returns the Prometheus metrics of the calculator, if there are some.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Option<&Arc<Metrics>> {
+        self.inner.metrics()
+    }
+
+    /// Sets the start and end coordinates directly, without geocoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_coords` - The starting coordinates, or a `(latitude, longitude)` pair.
+    /// * `end_coords` - The ending coordinates, or a `(latitude, longitude)` pair.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `AsyncWazeRouteCalculator` instance.
+    pub fn set_coords(
+        &mut self,
+        start_coords: impl Into<Coordinates>,
+        end_coords: impl Into<Coordinates>,
+    ) -> &mut Self {
+        self.inner.set_coords(start_coords, end_coords);
+        self
+    }
+
+    /// Sets the via points, the ordered intermediate stops between the start and the end.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The via points coordinates, or `(latitude, longitude)` pairs.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `AsyncWazeRouteCalculator` instance.
+    pub fn set_via_coords<T: Into<Coordinates>>(
+        &mut self,
+        coords: impl IntoIterator<Item = T>,
+    ) -> &mut Self {
+        self.inner.set_via_coords(coords);
+        self
+    }
+
+    /// Sets the start coordinates directly, without geocoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The starting coordinates, or a `(latitude, longitude)` pair.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `AsyncWazeRouteCalculator` instance.
+    pub fn set_start_coords(&mut self, coords: impl Into<Coordinates>) -> &mut Self {
+        self.inner.set_start_coords(coords);
+        self
+    }
+
+    /// Sets the end coordinates directly, without geocoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The ending coordinates, or a `(latitude, longitude)` pair.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `AsyncWazeRouteCalculator` instance.
+    pub fn set_end_coords(&mut self, coords: impl Into<Coordinates>) -> &mut Self {
+        self.inner.set_end_coords(coords);
+        self
+    }
+
+    /// Sets the departure time of the routes.
+    ///
+    /// # Arguments
+    ///
+    /// * `departure_time` - When the routes start.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `AsyncWazeRouteCalculator` instance.
+    pub fn set_departure_time(&mut self, departure_time: impl Into<DepartureTime>) -> &mut Self {
+        self.inner.departure_time = departure_time.into();
+        self
+    }
+}
+
+impl AsyncWazeRouteCalculator {
+    /// Sets the start and end coordinates based on the provided addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_address` - The starting address.
+    /// * `end_address` - The ending address.
+    ///
+    /// # Returns
+    ///
+    /// A result containing a mutable reference to the `AsyncWazeRouteCalculator` instance or an error.
+    pub async fn set_address(
+        &mut self,
+        start_address: &str,
+        end_address: &str,
+    ) -> Result<&mut Self, WazeRouteCalculatorError> {
+        let start_coords = self.address_to_coords(start_address).await?;
+        let end_coords = self.address_to_coords(end_address).await?;
+
+        debug!(
+            "Start coordinates: {}, {}",
+            start_coords.latitude, start_coords.longitude
+        );
+
+        debug!(
+            "End coordinates: {}, {}",
+            end_coords.latitude, end_coords.longitude
+        );
+
+        self.inner.start_coords = Some(start_coords);
+        self.inner.end_coords = Some(end_coords);
+
+        Ok(self)
+    }
+
+    /// Converts an address to coordinates.
+    ///
+    /// Coordinate strings, like `"32.08,34.78"` or DMS notation, are parsed locally
+    /// without calling the search server.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to convert.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the coordinates or an error.
+    pub async fn address_to_coords(
+        &self,
+        address: &str,
+    ) -> Result<Coordinates, WazeRouteCalculatorError> {
+        if let Some(coords) = parse_coordinates(address) {
+            debug!("Address is already coordinates: {:?}", coords);
+            return Ok(coords);
+        }
+
+        if let Some(coords) = self.inner.cached_coords(address) {
+            return Ok(coords);
+        }
+
+        let address_answer = self.search(address, None).await?;
+        let coords = WazeRouteCalculator::parse_coords_answer(address_answer, address)?;
+        self.inner.cache_coords(address, coords);
+
+        Ok(coords)
+    }
+
+    /// Geocodes an address into all the candidate addresses returned by Waze.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to geocode.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the candidate addresses in the order Waze ranked them, or an error.
+    pub async fn geocode(
+        &self,
+        address: &str,
+    ) -> Result<WazeAddressAnswer, WazeRouteCalculatorError> {
+        let address_answer = self.search(address, None).await?;
+
+        WazeRouteCalculator::parse_address_answer(address_answer)
+    }
+
+    /// Converts coordinates to the nearest address.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The coordinates to convert.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the nearest address or an error.
+    pub async fn coords_to_address(
+        &self,
+        coords: &Coordinates,
+    ) -> Result<WazeAddress, WazeRouteCalculatorError> {
+        let query = format!("{}, {}", coords.latitude, coords.longitude);
+        let address_answer = self.search(&query, Some(coords.into())).await?;
+
+        WazeRouteCalculator::parse_reverse_answer(address_answer, coords)
+    }
+
+    /// Sends a request through the transport, respecting the rate limiter and retrying it
+    /// according to the retry policy.
+    async fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        let mut attempt = 1;
+
+        loop {
+            tokio::time::sleep(self.inner.rate_limit_delay(request.endpoint)).await;
+
+            debug!("Attempt {}: {}", attempt, request.url);
+            let started = Instant::now();
+            let result = self.inner.async_transport.send(request).await;
+            self.inner.observe_request(request.endpoint, started);
+
+            let Some(delay) = self.inner.retry_delay(attempt, &result) else {
+                return result;
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn search(
+        &self,
+        address: &str,
+        near: Option<WazeAddressCoordinates>,
+    ) -> Result<Value, WazeRouteCalculatorError> {
+        let request = self.inner.coords_request(address, near);
+
+        let result = self
+            .send(&request)
+            .await
+            .and_then(|response| WazeRouteCalculator::parse_response(&request, response));
+
+        self.inner.observe_result(Endpoint::Search, result)
+    }
+
+    async fn get_route(
+        &self,
+        route_request: &RouteRequest,
+        at: i64,
+        n_paths: usize,
+    ) -> Result<Vec<WazeRoute>, WazeRouteCalculatorError> {
+        if let Some(routes) = self.inner.cached_route(route_request, at, n_paths) {
+            return Ok(routes);
+        }
+
+        let request = self.inner.route_request(route_request, at, n_paths);
+
+        let result = self
+            .send(&request)
+            .await
+            .and_then(|query_res| WazeRouteCalculator::parse_response(&request, query_res))
+            .and_then(|waze_route_answer| {
+                WazeRouteCalculator::parse_route_answer(&waze_route_answer)
+            });
+
+        let routes = self.inner.observe_result(Endpoint::Routing, result)?;
+        self.inner.cache_route(route_request, at, n_paths, &routes);
+
+        Ok(routes)
+    }
+
+    /// Calculates the best route of a `RouteRequest`, without changing the calculator.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, with its option overrides.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `RouteSummary` of the best route, or an error.
+    pub async fn route(
+        &self,
+        request: &RouteRequest,
+    ) -> Result<RouteSummary, WazeRouteCalculatorError> {
+        let now = SystemTime::now();
+        let departure_time = request.departure_time.unwrap_or(self.inner.departure_time);
+        let departure = departure_time.to_system_time(now)?;
+        let routes = self
+            .get_route(request, departure_time.to_minutes_offset(now), 1)
+            .await?;
+
+        self.inner.summarize_best_route(routes, departure)
+    }
+
+    /// Calculates the best route by calling `get_route` and summing up its segments.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `RouteSummary` of the best route, or an error.
+    pub async fn calculate_route(&self) -> Result<RouteSummary, WazeRouteCalculatorError> {
+        let (start, end) = self.inner.route_coords()?;
+
+        self.route(&RouteRequest::new(start, end)).await
+    }
+
+    /// Calculates up to `n_paths` alternative routes, each with its own time, distance, name and segments.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_paths` - The number of alternative routes to ask Waze for.
+    ///
+    /// # Returns
+    ///
+    /// A result containing a `RouteSummary` for each route alternative, or an error.
+    pub async fn calculate_routes(
+        &self,
+        n_paths: usize,
+    ) -> Result<Vec<RouteSummary>, WazeRouteCalculatorError> {
+        let now = SystemTime::now();
+        let (start, end) = self.inner.route_coords()?;
+        let departure = self.inner.departure_time.to_system_time(now)?;
+        let routes = self
+            .get_route(
+                &RouteRequest::new(start, end),
+                self.inner.departure_time.to_minutes_offset(now),
+                n_paths,
+            )
+            .await?;
+
+        Ok(self.inner.summarize_routes(routes, departure))
+    }
+
+    /// Sets the via points based on the provided addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses` - The ordered intermediate stops.
+    ///
+    /// # Returns
+    ///
+    /// A result containing a mutable reference to the `AsyncWazeRouteCalculator` instance or an error.
+    pub async fn set_via_addresses(
+        &mut self,
+        addresses: &[&str],
+    ) -> Result<&mut Self, WazeRouteCalculatorError> {
+        let mut via_coords = vec![];
+        for address in addresses {
+            via_coords.push(self.address_to_coords(address).await?);
+        }
+        self.inner.via_coords = via_coords;
+
+        debug!("Via coordinates: {:?}", self.inner.via_coords);
+
+        Ok(self)
+    }
+
+    /// Calculates the route from the start through the via points to the end.
+    ///
+    /// Each leg is a separate routing request, departing when the previous leg arrives.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `MultiLegRoute` with the per-leg breakdown and totals, or an error.
+    pub async fn calculate_route_via(&self) -> Result<MultiLegRoute, WazeRouteCalculatorError> {
+        let now = SystemTime::now();
+        let mut departure_time = self.inner.departure_time.to_system_time(now)?;
+        let mut legs = vec![];
+
+        for (from, to) in self.inner.legs()? {
+            let at = DepartureTime::At(departure_time).to_minutes_offset(now);
+            let routes = self.get_route(&RouteRequest::new(from, to), at, 1).await?;
+            let leg = self.inner.summarize_best_route(routes, departure_time)?;
+
+            departure_time = leg.arrival_time;
+            legs.push(leg);
+        }
+
+        Ok(MultiLegRoute::from(legs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{Region, VehicleType};
+    use crate::test_fixtures::route_answer;
+
+    #[tokio::test]
+    async fn test_async_address_to_coords() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url() + "/";
+
+        let mock = server
+            .mock("GET", "/SearchServer/mozi")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"bounds":{"top":1.0,"bottom":2.0,"left":4.0,"right":3.0},"businessName":null,"city":"Detroit","countryName":"United States","location":{"lat":12.34,"lon":56.78},"name":"Address","number":null,"provider":"waze","segmentId":-1,"state":null,"stateName":"Michigan","street":"Beaubien St","streetId":1601804}]"#)
+            .match_query(mockito::Matcher::Any)
+            .create_async()
+            .await;
+
+        let calculator = WazeRouteCalculator::builder()
+            .set_region(Region::US)
+            .set_vehicle_type(VehicleType::CAR)
+            .set_base_url(url.as_str())
+            .build_async();
+
+        let coords = calculator.address_to_coords("Test Address").await.unwrap();
+
+        mock.assert_async().await;
+
+        pretty_assertions::assert_eq!(coords.latitude, 12.34);
+        pretty_assertions::assert_eq!(coords.longitude, 56.78);
+        let bound = coords.bound.unwrap();
+        pretty_assertions::assert_eq!(bound.top, 2.0);
+        pretty_assertions::assert_eq!(bound.left, 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_async_calculate_route() {
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url() + "/";
+
+        let mock = server
+            .mock("GET", "/RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 180, 150, 2000))
+            .match_query(mockito::Matcher::Any)
+            .create_async()
+            .await;
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_region(Region::US)
+            .set_base_url(url.as_str())
+            .build_async();
+        calculator.set_coords(Coordinates::default(), Coordinates::default());
+        pretty_assertions::assert_eq!(calculator.start_coords(), Some(Coordinates::default()));
+
+        let summary = calculator.calculate_route().await.unwrap();
+
+        mock.assert_async().await;
+
+        pretty_assertions::assert_eq!(summary.duration, std::time::Duration::from_secs(180));
+        pretty_assertions::assert_eq!(summary.free_flow_duration.as_secs(), 150);
+        pretty_assertions::assert_eq!(summary.distance, 2000);
+    }
+
+    #[tokio::test]
+    async fn test_async_fake_transport() {
+        use crate::transport::FakeTransport;
+
+        let transport = FakeTransport::new().with_response(
+            "routingRequest",
+            TransportResponse::new(200, route_answer("", 600, 300, 1000)),
+        );
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_async_transport(transport)
+            .build_async();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        let summary = calculator.calculate_route().await.unwrap();
+        pretty_assertions::assert_eq!(summary.distance, 1000);
+        pretty_assertions::assert_eq!(summary.duration.as_secs(), 600);
+    }
+}
@@ -0,0 +1,1032 @@
+//! `waze` - calculate routes, geocode addresses and watch commutes from the command line.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, Level};
+use waze_rs::commute_watcher::{CommandSink, CommuteWatcher, WatchedRoute, WebhookSink};
+use waze_rs::helpers::{parse_coordinates, DepartureTime, Region, VehicleType};
+#[cfg(feature = "metrics")]
+use waze_rs::metrics::Metrics;
+use waze_rs::route_options::RouteOptions;
+use waze_rs::waze_route_calculator::{
+    RouteRequest, RouteSummary, WazeAddress, WazeRouteCalculator, WazeRouteCalculatorBuilder,
+    WazeRouteCalculatorError,
+};
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0   Success
+  1   Unknown error
+  2   Invalid arguments
+  3   Address or coordinates not found
+  4   No route found, or Waze API error
+  5   Unexpected HTTP status
+  6   Rate limited or blocked by Waze (HTTP 429, captcha page)
+  7   Network or transport error
+  8   Invalid Waze response
+  9   Alert notification failed
+  64  Missing coordinates
+  74  I/O error
+  78  Invalid configuration";
+
+/// Calculate actual route time and distance with the Waze API.
+#[derive(Debug, Parser)]
+#[command(name = "waze", version, after_help = EXIT_CODES)]
+struct Cli {
+    #[command(flatten)]
+    settings: Settings,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// The calculator settings, read from the arguments, the environment, or the config file.
+#[derive(Debug, Default, Args)]
+struct Settings {
+    /// TOML config file with default settings
+    #[arg(long, global = true, env = "WAZE_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Waze server region: US, EU, IL or AU [default: EU]
+    #[arg(short, long, global = true, env = "WAZE_REGION")]
+    region: Option<Region>,
+
+    /// Vehicle type: CAR, TAXI or MOTORCYCLE [default: CAR]
+    #[arg(long, global = true, env = "WAZE_VEHICLE_TYPE")]
+    vehicle_type: Option<VehicleType>,
+
+    /// Avoid toll roads
+    #[arg(long, global = true, env = "WAZE_AVOID_TOLL_ROADS", num_args = 0..=1, default_missing_value = "true")]
+    avoid_toll_roads: Option<bool>,
+
+    /// Avoid ferries
+    #[arg(long, global = true, env = "WAZE_AVOID_FERRIES", num_args = 0..=1, default_missing_value = "true")]
+    avoid_ferries: Option<bool>,
+
+    /// Avoid highways
+    #[arg(long, global = true, env = "WAZE_AVOID_HIGHWAYS", num_args = 0..=1, default_missing_value = "true")]
+    avoid_highways: Option<bool>,
+
+    /// Avoid subscription roads
+    #[arg(long, global = true, env = "WAZE_AVOID_SUBSCRIPTION_ROADS", num_args = 0..=1, default_missing_value = "true")]
+    avoid_subscription_roads: Option<bool>,
+
+    /// Output format [default: table]
+    #[arg(short, long, global = true, env = "WAZE_FORMAT")]
+    format: Option<Format>,
+
+    /// Waze base URL
+    #[arg(long, global = true, env = "WAZE_BASE_URL", hide = true)]
+    base_url: Option<String>,
+
+    /// Log the requests sent to Waze
+    #[arg(short, long, global = true)]
+    verbose: bool,
+}
+
+/// The defaults read from the config file, overridden by the arguments and the environment.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    region: Option<String>,
+    vehicle_type: Option<String>,
+    avoid_toll_roads: Option<bool>,
+    avoid_ferries: Option<bool>,
+    avoid_highways: Option<bool>,
+    avoid_subscription_roads: Option<bool>,
+    format: Option<String>,
+    base_url: Option<String>,
+    #[serde(default)]
+    watch: Vec<WatchConfig>,
+}
+
+/// A route watched by the `watch` command, from a `[[watch]]` table of the config file.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WatchConfig {
+    name: Option<String>,
+    from: String,
+    to: String,
+    max_duration: Option<u64>,
+    max_increase: Option<f64>,
+}
+
+impl Config {
+    /// Loads a config file.
+    fn load(path: &Path) -> Result<Self, CliError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CliError::Config(format!("{}: {}", path.display(), e)))?;
+        toml::from_str(&text).map_err(|e| CliError::Config(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// The output format.
+#[derive(Copy, Clone, Debug, Default, PartialEq, ValueEnum)]
+enum Format {
+    /// Aligned columns
+    #[default]
+    Table,
+
+    /// JSON, one line per sample for `watch`
+    Json,
+
+    /// Comma separated values with a header line
+    Csv,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Calculate the route between two addresses or coordinates
+    Route {
+        /// The starting address or coordinates
+        from: String,
+
+        /// The ending address or coordinates
+        to: String,
+
+        /// Intermediate stops, in order
+        #[arg(long)]
+        via: Vec<String>,
+
+        /// The number of alternative routes to calculate
+        #[arg(long, default_value_t = 1, conflicts_with = "via")]
+        alternatives: usize,
+
+        /// Leave in the given number of minutes (negative for the past)
+        #[arg(long, allow_negative_numbers = true)]
+        depart_in: Option<i64>,
+    },
+
+    /// List the candidate coordinates of an address
+    Geocode {
+        /// The address to geocode
+        address: String,
+    },
+
+    /// Find the address nearest to coordinates
+    Reverse {
+        /// The coordinates, e.g. "32.08,34.78"
+        coordinates: String,
+    },
+
+    /// Calculate the routes between every origin and every destination
+    Matrix {
+        /// An origin address or coordinates, repeated for each origin
+        #[arg(long = "from", required = true)]
+        origins: Vec<String>,
+
+        /// A destination address or coordinates, repeated for each destination
+        #[arg(long = "to", required = true)]
+        destinations: Vec<String>,
+
+        /// The maximum number of requests in flight
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+    },
+
+    /// Recalculate routes periodically and alert when they get slow
+    ///
+    /// Without FROM and TO, the `[[watch]]` routes of the config file are watched. Alerts are
+    /// printed in table format, and sent to every --webhook and --exec sink.
+    Watch {
+        /// The starting address or coordinates
+        #[arg(requires = "to")]
+        from: Option<String>,
+
+        /// The ending address or coordinates
+        to: Option<String>,
+
+        /// The number of seconds between two calculations
+        #[arg(long, default_value_t = 300)]
+        interval: u64,
+
+        /// Stop after this number of calculations
+        #[arg(long)]
+        count: Option<usize>,
+
+        /// Alert when a route takes more than this number of minutes
+        #[arg(long)]
+        max_duration: Option<u64>,
+
+        /// Alert when a route duration increases by more than this percentage between two calculations
+        #[arg(long)]
+        max_increase: Option<f64>,
+
+        /// POST the alerts as JSON to this URL, repeated for each webhook
+        #[arg(long)]
+        webhook: Vec<String>,
+
+        /// Run this shell command for each alert, with the alert as JSON on its standard input
+        #[arg(long)]
+        exec: Vec<String>,
+
+        /// Serve Prometheus metrics on this address, e.g. "0.0.0.0:9898"
+        #[cfg(feature = "metrics")]
+        #[arg(long, value_name = "ADDR")]
+        metrics: Option<String>,
+    },
+}
+
+/// CLI error types.
+#[derive(Error, Debug)]
+enum CliError {
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    #[error("Invalid coordinates: {0}")]
+    InvalidCoordinates(String),
+
+    #[error(transparent)]
+    Waze(#[from] WazeRouteCalculatorError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl CliError {
+    /// Returns the process exit code of the error, as listed in `EXIT_CODES`.
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Config(_) => 78,
+            CliError::InvalidCoordinates(_) => 2,
+            CliError::Io(_) => 74,
+            CliError::Waze(error) => exit_code(error),
+        }
+    }
+}
+
+/// Maps a calculator error to a process exit code, as listed in `EXIT_CODES`.
+fn exit_code(error: &WazeRouteCalculatorError) -> u8 {
+    match error {
+        WazeRouteCalculatorError::NoResults(_) => 3,
+        WazeRouteCalculatorError::FailedToGetRoute | WazeRouteCalculatorError::WazeApiError(_) => 4,
+        WazeRouteCalculatorError::HttpStatus { status: 429, .. }
+        | WazeRouteCalculatorError::HtmlResponse { .. } => 6,
+        WazeRouteCalculatorError::HttpStatus { .. } => 5,
+        WazeRouteCalculatorError::NetworkError(_) | WazeRouteCalculatorError::TransportError(_) => {
+            7
+        }
+        WazeRouteCalculatorError::InvalidResponse(_)
+        | WazeRouteCalculatorError::SerializationError(_) => 8,
+        WazeRouteCalculatorError::InvalidDepartureTime(_) => 2,
+        WazeRouteCalculatorError::MissingCoordinates(_) => 64,
+        WazeRouteCalculatorError::NotificationError(_) => 9,
+        WazeRouteCalculatorError::IoError(_) => 74,
+        WazeRouteCalculatorError::UnknownError => 1,
+    }
+}
+
+/// A line of output, printed as a table row, a CSV line or a JSON object.
+trait Record: Serialize {
+    /// The column names.
+    const HEADERS: &'static [&'static str];
+
+    /// The column values, in the `HEADERS` order.
+    fn row(&self) -> Vec<String>;
+}
+
+/// Rounds a value to the given number of decimals, to keep the output readable.
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Converts a duration to minutes, rounded to a tenth.
+fn minutes(duration: Duration) -> f64 {
+    round(duration.as_secs_f64() / 60.0, 1)
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct RouteRecord {
+    route: String,
+    duration_min: f64,
+    free_flow_min: f64,
+    delay_min: f64,
+    distance_km: f64,
+}
+
+impl RouteRecord {
+    fn new(route: impl Into<String>, summary: &RouteSummary) -> Self {
+        RouteRecord {
+            route: route.into(),
+            duration_min: minutes(summary.duration),
+            free_flow_min: minutes(summary.free_flow_duration),
+            delay_min: minutes(summary.traffic_delay),
+            distance_km: round(summary.distance_km(), 2),
+        }
+    }
+}
+
+impl Record for RouteRecord {
+    const HEADERS: &'static [&'static str] = &[
+        "route",
+        "duration_min",
+        "free_flow_min",
+        "delay_min",
+        "distance_km",
+    ];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.route.clone(),
+            self.duration_min.to_string(),
+            self.free_flow_min.to_string(),
+            self.delay_min.to_string(),
+            self.distance_km.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct AddressRecord {
+    name: String,
+    city: String,
+    country: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl From<&WazeAddress> for AddressRecord {
+    fn from(address: &WazeAddress) -> Self {
+        AddressRecord {
+            name: address.name.clone(),
+            city: address.city.clone().unwrap_or_default(),
+            country: address.country_name.clone().unwrap_or_default(),
+            latitude: address.location.lat,
+            longitude: address.location.lon,
+        }
+    }
+}
+
+impl Record for AddressRecord {
+    const HEADERS: &'static [&'static str] = &["name", "city", "country", "latitude", "longitude"];
+
+    fn row(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.city.clone(),
+            self.country.clone(),
+            self.latitude.to_string(),
+            self.longitude.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct MatrixRecord {
+    origin: String,
+    destination: String,
+    duration_min: Option<f64>,
+    distance_km: Option<f64>,
+    error: Option<String>,
+}
+
+impl Record for MatrixRecord {
+    const HEADERS: &'static [&'static str] = &[
+        "origin",
+        "destination",
+        "duration_min",
+        "distance_km",
+        "error",
+    ];
+
+    fn row(&self) -> Vec<String> {
+        let cell = |value: Option<f64>| value.map(|x| x.to_string()).unwrap_or_default();
+        vec![
+            self.origin.clone(),
+            self.destination.clone(),
+            cell(self.duration_min),
+            cell(self.distance_km),
+            self.error.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct WatchRecord {
+    time: u64,
+    name: String,
+    #[serde(flatten)]
+    route: RouteRecord,
+}
+
+impl Record for WatchRecord {
+    const HEADERS: &'static [&'static str] = &[
+        "time",
+        "name",
+        "route",
+        "duration_min",
+        "free_flow_min",
+        "delay_min",
+        "distance_km",
+    ];
+
+    fn row(&self) -> Vec<String> {
+        let mut row = vec![self.time.to_string(), self.name.clone()];
+        row.extend(self.route.row());
+        row
+    }
+}
+
+/// Quotes a CSV field if needed.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Renders records in the given format.
+fn render<R: Record>(records: &[R], format: Format) -> Result<String, CliError> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(records)
+            .map_err(WazeRouteCalculatorError::from)?
+            + "\n"),
+        _ => render_rows(records, format, true),
+    }
+}
+
+/// Renders records as rows, with an optional header, or as JSON lines.
+fn render_rows<R: Record>(records: &[R], format: Format, header: bool) -> Result<String, CliError> {
+    let headers = R::HEADERS.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    let rows = header
+        .then(|| headers.clone())
+        .into_iter()
+        .chain(records.iter().map(Record::row))
+        .collect::<Vec<_>>();
+
+    let mut output = String::new();
+    match format {
+        Format::Json => {
+            for record in records {
+                output += &serde_json::to_string(record).map_err(WazeRouteCalculatorError::from)?;
+                output += "\n";
+            }
+        }
+        Format::Csv => {
+            for row in rows {
+                output += &row
+                    .iter()
+                    .map(|x| csv_escape(x))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                output += "\n";
+            }
+        }
+        Format::Table => {
+            let widths = headers
+                .iter()
+                .enumerate()
+                .map(|(index, name)| {
+                    rows.iter()
+                        .map(|row| row[index].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                        .max(name.len())
+                })
+                .collect::<Vec<_>>();
+            for row in rows {
+                let line = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                output += line.trim_end();
+                output += "\n";
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// The settings merged from the arguments, the environment and the config file.
+#[derive(Debug)]
+struct Resolved {
+    region: Region,
+    vehicle_type: VehicleType,
+    route_options: RouteOptions,
+    avoid_subscription_roads: bool,
+    format: Format,
+    base_url: Option<String>,
+    watch: Vec<WatchConfig>,
+}
+
+impl Settings {
+    /// Merges the settings with the config file, the arguments and the environment taking precedence.
+    fn resolve(&self) -> Result<Resolved, CliError> {
+        let config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        let region = match (self.region, &config.region) {
+            (Some(region), _) => region,
+            (None, Some(region)) => region.parse().map_err(CliError::Config)?,
+            (None, None) => Region::EU,
+        };
+        let vehicle_type = match (self.vehicle_type, &config.vehicle_type) {
+            (Some(vehicle_type), _) => vehicle_type,
+            (None, Some(vehicle_type)) => vehicle_type.parse().map_err(CliError::Config)?,
+            (None, None) => VehicleType::CAR,
+        };
+        let format = match (self.format, &config.format) {
+            (Some(format), _) => format,
+            (None, Some(format)) => Format::from_str(format, true).map_err(CliError::Config)?,
+            (None, None) => Format::Table,
+        };
+
+        let mut route_options = RouteOptions::default();
+        if let Some(value) = self.avoid_toll_roads.or(config.avoid_toll_roads) {
+            route_options.avoid_toll_roads = Some(value);
+        }
+        if let Some(value) = self.avoid_ferries.or(config.avoid_ferries) {
+            route_options.avoid_ferries = Some(value);
+        }
+        if let Some(value) = self.avoid_highways.or(config.avoid_highways) {
+            route_options.avoid_highways = Some(value);
+        }
+
+        Ok(Resolved {
+            region,
+            vehicle_type,
+            route_options,
+            avoid_subscription_roads: self
+                .avoid_subscription_roads
+                .or(config.avoid_subscription_roads)
+                .unwrap_or(false),
+            format,
+            base_url: self.base_url.clone().or(config.base_url),
+            watch: config.watch,
+        })
+    }
+}
+
+impl Resolved {
+    /// Builds a calculator with these settings.
+    fn calculator(&self, departure_time: DepartureTime) -> WazeRouteCalculator {
+        self.builder(departure_time).build()
+    }
+
+    /// Returns a calculator builder with these settings.
+    fn builder(&self, departure_time: DepartureTime) -> WazeRouteCalculatorBuilder {
+        let mut builder = WazeRouteCalculator::builder()
+            .set_region(self.region)
+            .set_vehicle_type(self.vehicle_type)
+            .set_route_options(self.route_options.clone())
+            .set_avoid_subscription_roads(self.avoid_subscription_roads)
+            .set_departure_time(departure_time);
+        if let Some(base_url) = &self.base_url {
+            builder = builder.set_base_url(base_url);
+        }
+        builder
+    }
+}
+
+/// Returns a time in seconds since the Unix epoch.
+fn unix_time(time: std::time::SystemTime) -> u64 {
+    time.duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+/// Runs a command, writing its output to `out`.
+fn run(cli: Cli, out: &mut impl Write) -> Result<(), CliError> {
+    let settings = cli.settings.resolve()?;
+    debug!("Settings: {:?}", settings);
+
+    match cli.command {
+        Command::Route {
+            from,
+            to,
+            via,
+            alternatives,
+            depart_in,
+        } => {
+            let departure_time = depart_in.map_or(DepartureTime::Now, DepartureTime::InMinutes);
+            let mut calculator = settings.calculator(departure_time);
+            calculator.set_address(&from, &to)?;
+
+            let records = if via.is_empty() {
+                calculator
+                    .calculate_routes(alternatives)?
+                    .iter()
+                    .map(|route| RouteRecord::new(route.route_name.clone(), route))
+                    .collect::<Vec<_>>()
+            } else {
+                let via = via.iter().map(String::as_str).collect::<Vec<_>>();
+                let route = calculator.set_via_addresses(&via)?.calculate_route_via()?;
+
+                let mut records = route
+                    .legs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, leg)| {
+                        RouteRecord::new(format!("leg {}: {}", index + 1, leg.route_name), leg)
+                    })
+                    .collect::<Vec<_>>();
+                records.push(RouteRecord {
+                    route: "total".to_string(),
+                    duration_min: minutes(route.duration),
+                    free_flow_min: minutes(route.free_flow_duration),
+                    delay_min: minutes(route.traffic_delay),
+                    distance_km: round(route.distance as f64 / 1000.0, 2),
+                });
+                records
+            };
+            out.write_all(render(&records, settings.format)?.as_bytes())?;
+        }
+        Command::Geocode { address } => {
+            let calculator = settings.calculator(DepartureTime::Now);
+            let records = calculator
+                .geocode(&address)?
+                .iter()
+                .map(AddressRecord::from)
+                .collect::<Vec<_>>();
+            out.write_all(render(&records, settings.format)?.as_bytes())?;
+        }
+        Command::Reverse { coordinates } => {
+            let coords =
+                parse_coordinates(&coordinates).ok_or(CliError::InvalidCoordinates(coordinates))?;
+            let calculator = settings.calculator(DepartureTime::Now);
+            let address = calculator.coords_to_address(&coords)?;
+            out.write_all(render(&[AddressRecord::from(&address)], settings.format)?.as_bytes())?;
+        }
+        Command::Matrix {
+            origins,
+            destinations,
+            concurrency,
+        } => {
+            let calculator = settings.calculator(DepartureTime::Now);
+            let origins = origins.iter().map(String::as_str).collect::<Vec<_>>();
+            let destinations = destinations.iter().map(String::as_str).collect::<Vec<_>>();
+            let matrix = calculator.calculate_matrix(&origins, &destinations, concurrency);
+
+            let mut records = vec![];
+            for (origin, row) in matrix.origins.iter().zip(&matrix.cells) {
+                for (destination, cell) in matrix.destinations.iter().zip(row) {
+                    records.push(MatrixRecord {
+                        origin: origin.clone(),
+                        destination: destination.clone(),
+                        duration_min: cell.as_ref().ok().map(|x| minutes(x.duration)),
+                        distance_km: cell
+                            .as_ref()
+                            .ok()
+                            .map(|x| round(x.distance as f64 / 1000.0, 2)),
+                        error: cell.as_ref().err().map(|e| e.to_string()),
+                    });
+                }
+            }
+            out.write_all(render(&records, settings.format)?.as_bytes())?;
+        }
+        Command::Watch {
+            from,
+            to,
+            interval,
+            count,
+            max_duration,
+            max_increase,
+            webhook,
+            exec,
+            #[cfg(feature = "metrics")]
+            metrics,
+        } => {
+            let routes = match (from, to) {
+                (Some(from), Some(to)) => vec![WatchConfig {
+                    name: None,
+                    from,
+                    to,
+                    max_duration: None,
+                    max_increase: None,
+                }],
+                _ => settings.watch.clone(),
+            };
+            if routes.is_empty() {
+                return Err(CliError::Config(
+                    "no route to watch: give FROM and TO, or [[watch]] routes in the config file"
+                        .to_string(),
+                ));
+            }
+
+            #[allow(unused_mut)]
+            let mut builder = settings.builder(DepartureTime::Now);
+            #[cfg(feature = "metrics")]
+            if let Some(addr) = metrics {
+                let metrics = std::sync::Arc::new(Metrics::new());
+                metrics.clone().serve(addr.as_str())?;
+                builder = builder.set_metrics(metrics);
+            }
+            let calculator = builder.build();
+
+            let mut watched = vec![];
+            for route in routes {
+                let request = RouteRequest::new(
+                    calculator.address_to_coords(&route.from)?,
+                    calculator.address_to_coords(&route.to)?,
+                );
+                let name = route
+                    .name
+                    .unwrap_or_else(|| format!("{} - {}", route.from, route.to));
+                let mut watched_route = WatchedRoute::new(name, request);
+                if let Some(minutes) = route.max_duration.or(max_duration) {
+                    watched_route =
+                        watched_route.with_max_duration(Duration::from_secs(minutes * 60));
+                }
+                if let Some(percent) = route.max_increase.or(max_increase) {
+                    watched_route = watched_route.with_max_increase_percent(percent);
+                }
+                watched.push(watched_route);
+            }
+
+            let mut watcher =
+                CommuteWatcher::new(calculator).with_interval(Duration::from_secs(interval));
+            for route in watched {
+                watcher = watcher.with_route(route);
+            }
+            for url in webhook {
+                watcher = watcher.with_sink(WebhookSink::new(url)?);
+            }
+            for command in exec {
+                watcher = watcher.with_sink(CommandSink::shell(&command));
+            }
+
+            let mut result = Ok(());
+            let mut header = true;
+            watcher.run(count, |checks| {
+                let time = unix_time(std::time::SystemTime::now());
+                let mut records = vec![];
+                let mut alerts = String::new();
+                result = Ok(());
+                for check in checks {
+                    for alert in &check.alerts {
+                        alerts += &format!("{}\n", alert);
+                    }
+                    match check.result {
+                        Ok(route) => records.push(WatchRecord {
+                            time,
+                            name: check.route,
+                            route: RouteRecord::new(route.route_name.clone(), &route),
+                        }),
+                        Err(e) => {
+                            eprintln!("waze: {}: {}", check.route, e);
+                            result = Err(CliError::from(e));
+                        }
+                    }
+                }
+
+                let written = render_rows(&records, settings.format, header)
+                    .map(|rows| match settings.format {
+                        Format::Table => rows + &alerts,
+                        Format::Json | Format::Csv => rows,
+                    })
+                    .and_then(|rows| Ok(out.write_all(rows.as_bytes())?))
+                    .and_then(|()| Ok(out.flush()?));
+                header = false;
+                match written {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(e) => {
+                        result = Err(e);
+                        ControlFlow::Break(())
+                    }
+                }
+            });
+            // The exit code reflects the last check, earlier failures having been reported.
+            result?;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(if cli.settings.verbose {
+            Level::DEBUG
+        } else {
+            Level::WARN
+        })
+        .init();
+
+    match run(cli, &mut std::io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("waze: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "../test_fixtures.rs"]
+mod test_fixtures;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::route_answer;
+
+    fn run_args(url: &str, format: &str, args: &[&str]) -> Result<String, CliError> {
+        let mut cli_args = vec!["waze", "--region", "US", "--base-url", url, "-f", format];
+        cli_args.extend(args);
+
+        let mut out = vec![];
+        run(Cli::try_parse_from(cli_args).unwrap(), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        pretty_assertions::assert_eq!(
+            exit_code(&WazeRouteCalculatorError::NoResults("x".to_string())),
+            3
+        );
+        pretty_assertions::assert_eq!(
+            exit_code(&WazeRouteCalculatorError::HttpStatus {
+                status: 429,
+                url: String::new(),
+                query: String::new(),
+                body: String::new(),
+            }),
+            6
+        );
+        pretty_assertions::assert_eq!(
+            exit_code(&WazeRouteCalculatorError::HttpStatus {
+                status: 500,
+                url: String::new(),
+                query: String::new(),
+                body: String::new(),
+            }),
+            5
+        );
+        pretty_assertions::assert_eq!(
+            exit_code(&WazeRouteCalculatorError::MissingCoordinates("start")),
+            64
+        );
+        pretty_assertions::assert_eq!(CliError::Config(String::new()).exit_code(), 78);
+    }
+
+    #[test]
+    fn test_settings() {
+        let path = std::env::temp_dir().join(format!("waze-rs-cli-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "region = \"il\"\nvehicle_type = \"taxi\"\navoid_toll_roads = true\nformat = \"csv\"\n",
+        )
+        .unwrap();
+        let config = path.to_str().unwrap();
+
+        let cli = Cli::try_parse_from(["waze", "--config", config, "geocode", "x"]).unwrap();
+        let settings = cli.settings.resolve().unwrap();
+        assert!(matches!(settings.region, Region::IL));
+        pretty_assertions::assert_eq!(settings.vehicle_type, VehicleType::TAXI);
+        pretty_assertions::assert_eq!(settings.route_options.avoid_toll_roads, Some(true));
+        pretty_assertions::assert_eq!(settings.format, Format::Csv);
+
+        let cli = Cli::try_parse_from([
+            "waze",
+            "--config",
+            config,
+            "geocode",
+            "x",
+            "--region",
+            "US",
+            "--avoid-toll-roads=false",
+            "--avoid-ferries",
+            "-f",
+            "json",
+        ])
+        .unwrap();
+        let settings = cli.settings.resolve().unwrap();
+        assert!(matches!(settings.region, Region::US));
+        pretty_assertions::assert_eq!(settings.route_options.avoid_toll_roads, Some(false));
+        pretty_assertions::assert_eq!(settings.route_options.avoid_ferries, Some(true));
+        pretty_assertions::assert_eq!(settings.format, Format::Json);
+
+        std::fs::write(
+            &path,
+            "[[watch]]\nname = \"work\"\nfrom = \"Home\"\nto = \"Work\"\nmax_duration = 45\n",
+        )
+        .unwrap();
+        let cli = Cli::try_parse_from(["waze", "--config", config, "watch"]).unwrap();
+        pretty_assertions::assert_eq!(
+            cli.settings.resolve().unwrap().watch,
+            vec![WatchConfig {
+                name: Some("work".to_string()),
+                from: "Home".to_string(),
+                to: "Work".to_string(),
+                max_duration: Some(45),
+                max_increase: None,
+            }]
+        );
+
+        std::fs::write(&path, "region = \"mars\"\n").unwrap();
+        let cli = Cli::try_parse_from(["waze", "--config", config, "geocode", "x"]).unwrap();
+        pretty_assertions::assert_eq!(cli.settings.resolve().unwrap_err().exit_code(), 78);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_render() {
+        let records = vec![
+            MatrixRecord {
+                origin: "Tel Aviv".to_string(),
+                destination: "Haifa, Israel".to_string(),
+                duration_min: Some(61.5),
+                distance_km: Some(95.12),
+                error: None,
+            },
+            MatrixRecord {
+                origin: "Tel Aviv".to_string(),
+                destination: "Nowhere".to_string(),
+                duration_min: None,
+                distance_km: None,
+                error: Some("No results for Nowhere".to_string()),
+            },
+        ];
+
+        pretty_assertions::assert_eq!(
+            render(&records, Format::Csv).unwrap(),
+            "origin,destination,duration_min,distance_km,error\n\
+             Tel Aviv,\"Haifa, Israel\",61.5,95.12,\n\
+             Tel Aviv,Nowhere,,,No results for Nowhere\n"
+        );
+        pretty_assertions::assert_eq!(
+            render(&records, Format::Table).unwrap(),
+            "origin    destination    duration_min  distance_km  error\n\
+             Tel Aviv  Haifa, Israel  61.5          95.12\n\
+             Tel Aviv  Nowhere                                   No results for Nowhere\n"
+        );
+        pretty_assertions::assert_eq!(
+            render_rows(&records[..1], Format::Json, false).unwrap(),
+            "{\"origin\":\"Tel Aviv\",\"destination\":\"Haifa, Israel\",\"duration_min\":61.5,\"distance_km\":95.12,\"error\":null}\n"
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        server
+            .mock("GET", "/RoutingManager/routingRequest")
+            .with_status(200)
+            .with_body(route_answer("Route 1", 900, 600, 12345))
+            .match_query(mockito::Matcher::Any)
+            .create();
+        server
+            .mock("GET", "/SearchServer/mozi")
+            .with_status(200)
+            .with_body("[]")
+            .match_query(mockito::Matcher::Any)
+            .create();
+
+        let output = run_args(&url, "csv", &["route", "32.08,34.78", "31.768,35.214"]).unwrap();
+        pretty_assertions::assert_eq!(
+            output,
+            "route,duration_min,free_flow_min,delay_min,distance_km\nRoute 1,15,10,5,12.35\n"
+        );
+
+        let watch = ["watch", "32.08,34.78", "31.768,35.214", "--count", "2"];
+        let output = run_args(&url, "csv", &[&watch[..], &["--interval", "0"]].concat()).unwrap();
+        pretty_assertions::assert_eq!(output.lines().count(), 3);
+
+        let watch = [&watch[..], &["--interval", "0", "--max-duration", "10"]].concat();
+        let output = run_args(&url, "table", &watch).unwrap();
+        assert!(output.contains("ETA 15 min is above the 10 min threshold"));
+
+        let error = run_args(&url, "table", &["watch"]).unwrap_err();
+        pretty_assertions::assert_eq!(error.exit_code(), 78);
+
+        let matrix = ["matrix", "--from", "32.08,34.78", "--to", "31.768,35.214"];
+        let output = run_args(&url, "json", &[&matrix[..], &["--to", "Nowhere"]].concat()).unwrap();
+        let matrix: serde_json::Value = serde_json::from_str(&output).unwrap();
+        pretty_assertions::assert_eq!(matrix[0]["duration_min"], 15.0);
+        pretty_assertions::assert_eq!(matrix[1]["error"], "No results for Nowhere");
+
+        let error = run_args(&url, "table", &["route", "Nowhere", "32.08,34.78"]).unwrap_err();
+        pretty_assertions::assert_eq!(error.exit_code(), 3);
+
+        let error = run_args(&url, "table", &["reverse", "Nowhere"]).unwrap_err();
+        pretty_assertions::assert_eq!(error.exit_code(), 2);
+
+        let route = ["route", "32.08,34.78", "31.768,35.214"];
+        let depart_in = format!("{}", i64::MAX);
+        let error = run_args(
+            &url,
+            "table",
+            &[&route[..], &["--depart-in", &depart_in]].concat(),
+        )
+        .unwrap_err();
+        pretty_assertions::assert_eq!(error.exit_code(), 2);
+    }
+}
@@ -0,0 +1,752 @@
+use crate::waze_route_calculator::{
+    RouteRequest, RouteSummary, WazeRouteCalculator, WazeRouteCalculatorError,
+};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Debug;
+use std::io::{ErrorKind, Write};
+use std::ops::ControlFlow;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, warn};
+
+/// A route checked by a `CommuteWatcher`, with its alert thresholds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchedRoute {
+    /// The name of the route in the alerts, e.g. `"Home to work"`.
+    pub name: String,
+
+    /// The route query.
+    pub request: RouteRequest,
+
+    /// Alert when the realtime duration goes above this duration.
+    pub max_duration: Option<Duration>,
+
+    /// Alert when the realtime duration increases by more than this percentage between two checks.
+    pub max_increase_percent: Option<f64>,
+}
+
+impl WatchedRoute {
+    /// Creates a new `WatchedRoute` without alert thresholds.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the route in the alerts.
+    /// * `request` - The route query.
+    ///
+    /// # Returns
+    ///
+    /// A `WatchedRoute` instance.
+    pub fn new(name: impl Into<String>, request: RouteRequest) -> Self {
+        WatchedRoute {
+            name: name.into(),
+            request,
+            max_duration: None,
+            max_increase_percent: None,
+        }
+    }
+
+    /// Sets the duration above which an alert is fired.
+    pub fn with_max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Sets the increase between two checks, in percent, above which an alert is fired.
+    pub fn with_max_increase_percent(mut self, percent: f64) -> Self {
+        self.max_increase_percent = Some(percent);
+        self
+    }
+
+    /// Compares a new sample with the previous one.
+    ///
+    /// The threshold alert is only fired when the duration crosses the threshold, not on every
+    /// check above it.
+    fn alerts(&self, previous: Option<&RouteSample>, sample: &RouteSample) -> Vec<Alert> {
+        let mut alerts = vec![];
+
+        if let Some(threshold) = self.max_duration {
+            if sample.duration > threshold && previous.is_none_or(|x| x.duration <= threshold) {
+                alerts.push(AlertKind::Threshold { threshold });
+            }
+        }
+
+        if let (Some(max_increase), Some(previous)) = (self.max_increase_percent, previous) {
+            if !previous.duration.is_zero() {
+                let percent =
+                    (sample.duration.as_secs_f64() / previous.duration.as_secs_f64() - 1.0) * 100.0;
+                if percent > max_increase {
+                    alerts.push(AlertKind::Jump {
+                        previous: previous.duration,
+                        percent,
+                    });
+                }
+            }
+        }
+
+        alerts
+            .into_iter()
+            .map(|kind| Alert {
+                route: self.name.clone(),
+                kind,
+                sample: *sample,
+            })
+            .collect()
+    }
+}
+
+/// A route check result, kept in the `RouteHistory`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RouteSample {
+    /// When the route was checked.
+    pub time: SystemTime,
+
+    /// The route duration with realtime traffic.
+    pub duration: Duration,
+
+    /// The route duration without realtime traffic.
+    pub free_flow_duration: Duration,
+
+    /// The extra time caused by traffic.
+    pub traffic_delay: Duration,
+
+    /// The route distance in meters.
+    pub distance: u64,
+}
+
+impl RouteSample {
+    /// Creates a new `RouteSample` from a route checked at `time`.
+    pub fn new(time: SystemTime, summary: &RouteSummary) -> Self {
+        RouteSample {
+            time,
+            duration: summary.duration,
+            free_flow_duration: summary.free_flow_duration,
+            traffic_delay: summary.traffic_delay,
+            distance: summary.distance,
+        }
+    }
+}
+
+/// The latest samples of a route, the oldest ones being dropped past the capacity.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouteHistory {
+    samples: VecDeque<RouteSample>,
+    capacity: usize,
+}
+
+impl RouteHistory {
+    /// Creates an empty `RouteHistory` keeping up to `capacity` samples.
+    pub fn new(capacity: usize) -> Self {
+        RouteHistory {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Adds a sample, dropping the oldest one if the history is full.
+    pub fn push(&mut self, sample: RouteSample) {
+        if self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        if self.capacity > 0 {
+            self.samples.push_back(sample);
+        }
+    }
+
+    /// Returns the samples, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = &RouteSample> {
+        self.samples.iter()
+    }
+
+    /// Returns the latest sample.
+    pub fn latest(&self) -> Option<&RouteSample> {
+        self.samples.back()
+    }
+
+    /// Returns the number of samples.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Checks if there is no sample.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// The reason of an `Alert`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlertKind {
+    /// The duration went above the route `max_duration`.
+    Threshold { threshold: Duration },
+
+    /// The duration increased by more than the route `max_increase_percent` since the previous check.
+    Jump { previous: Duration, percent: f64 },
+}
+
+/// A notification fired by a `CommuteWatcher`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    /// The name of the route.
+    pub route: String,
+
+    /// The reason of the alert.
+    pub kind: AlertKind,
+
+    /// The sample that fired the alert.
+    pub sample: RouteSample,
+}
+
+impl Alert {
+    /// Converts the alert to JSON, with durations in seconds, distances in meters and times in
+    /// seconds since the Unix epoch.
+    ///
+    /// # Returns
+    ///
+    /// A JSON object, the body of the webhook requests.
+    pub fn to_json(&self) -> Value {
+        let mut alert = json!({
+            "route": self.route,
+            "message": self.to_string(),
+            "time": self
+                .sample
+                .time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            "duration": self.sample.duration.as_secs(),
+            "freeFlowDuration": self.sample.free_flow_duration.as_secs(),
+            "trafficDelay": self.sample.traffic_delay.as_secs(),
+            "distance": self.sample.distance,
+        });
+
+        match self.kind {
+            AlertKind::Threshold { threshold } => {
+                alert["kind"] = json!("threshold");
+                alert["threshold"] = json!(threshold.as_secs());
+            }
+            AlertKind::Jump { previous, percent } => {
+                alert["kind"] = json!("jump");
+                alert["previousDuration"] = json!(previous.as_secs());
+                alert["increasePercent"] = json!(percent);
+            }
+        }
+
+        alert
+    }
+}
+
+/// Formats a duration in minutes.
+fn minutes(duration: Duration) -> String {
+    format!("{:.0} min", duration.as_secs_f64() / 60.0)
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            AlertKind::Threshold { threshold } => write!(
+                f,
+                "{}: ETA {} is above the {} threshold",
+                self.route,
+                minutes(self.sample.duration),
+                minutes(threshold)
+            ),
+            AlertKind::Jump { previous, percent } => write!(
+                f,
+                "{}: ETA jumped {:.0}% from {} to {}",
+                self.route,
+                percent,
+                minutes(previous),
+                minutes(self.sample.duration)
+            ),
+        }
+    }
+}
+
+/// Delivers the alerts of a `CommuteWatcher`.
+pub trait AlertSink: Debug + Send + Sync {
+    /// Delivers an alert.
+    ///
+    /// # Arguments
+    ///
+    /// * `alert` - The alert to deliver.
+    ///
+    /// # Returns
+    ///
+    /// A result indicating whether the alert was delivered.
+    fn notify(&self, alert: &Alert) -> Result<(), WazeRouteCalculatorError>;
+}
+
+/// Prints the alerts on the standard output.
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl AlertSink for StdoutSink {
+    fn notify(&self, alert: &Alert) -> Result<(), WazeRouteCalculatorError> {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", alert)?;
+        Ok(())
+    }
+}
+
+/// POSTs the alerts as JSON, see `Alert::to_json()`, to a webhook.
+#[derive(Debug)]
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::blocking::Client,
+}
+
+impl WebhookSink {
+    /// Creates a new `WebhookSink`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The webhook URL.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `WebhookSink` instance or an error if the HTTP client can't be built.
+    pub fn new(url: impl Into<String>) -> Result<Self, WazeRouteCalculatorError> {
+        Ok(WebhookSink::with_client(
+            url,
+            reqwest::blocking::Client::builder().build()?,
+        ))
+    }
+
+    /// Creates a new `WebhookSink` sending the requests with a user provided client.
+    pub fn with_client(url: impl Into<String>, client: reqwest::blocking::Client) -> Self {
+        WebhookSink {
+            url: url.into(),
+            client,
+        }
+    }
+}
+
+impl AlertSink for WebhookSink {
+    fn notify(&self, alert: &Alert) -> Result<(), WazeRouteCalculatorError> {
+        let response = self.client.post(&self.url).json(&alert.to_json()).send()?;
+
+        if !response.status().is_success() {
+            return Err(WazeRouteCalculatorError::NotificationError(format!(
+                "Webhook {} answered {}",
+                self.url,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Runs a command for each alert.
+///
+/// The alert JSON, see `Alert::to_json()`, is written to the command standard input, and the
+/// route name and the alert message are set in the `WAZE_ALERT_ROUTE` and `WAZE_ALERT_MESSAGE`
+/// environment variables.
+#[derive(Clone, Debug)]
+pub struct CommandSink {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandSink {
+    /// Creates a new `CommandSink`.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to run.
+    /// * `args` - The program arguments.
+    ///
+    /// # Returns
+    ///
+    /// A `CommandSink` instance.
+    pub fn new(program: impl Into<String>, args: &[&str]) -> Self {
+        CommandSink {
+            program: program.into(),
+            args: args.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    /// Creates a new `CommandSink` running a shell command line, with `sh -c` or `cmd /C`.
+    pub fn shell(command: &str) -> Self {
+        if cfg!(windows) {
+            CommandSink::new("cmd", &["/C", command])
+        } else {
+            CommandSink::new("sh", &["-c", command])
+        }
+    }
+}
+
+impl AlertSink for CommandSink {
+    fn notify(&self, alert: &Alert) -> Result<(), WazeRouteCalculatorError> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("WAZE_ALERT_ROUTE", &alert.route)
+            .env("WAZE_ALERT_MESSAGE", alert.to_string())
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        let written = child.stdin.take().map_or(Ok(()), |mut stdin| {
+            stdin.write_all(alert.to_json().to_string().as_bytes())
+        });
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(WazeRouteCalculatorError::NotificationError(format!(
+                "{} exited with {}",
+                self.program, status
+            )));
+        }
+
+        // A command that does not read the alert JSON closes its input before we write it
+        match written {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The result of a route check.
+#[derive(Debug)]
+pub struct RouteCheck {
+    /// The name of the route.
+    pub route: String,
+
+    /// The checked route, or the error that prevented the check.
+    pub result: Result<RouteSummary, WazeRouteCalculatorError>,
+
+    /// The alerts fired by the check.
+    pub alerts: Vec<Alert>,
+}
+
+/// A watched route and its history.
+#[derive(Debug)]
+struct WatchState {
+    route: WatchedRoute,
+    history: RouteHistory,
+}
+
+/// Recalculates a set of routes on a schedule, keeping a rolling history of each one and firing
+/// alerts through the sinks when a duration goes above its threshold or jumps.
+///
+/// With the `metrics` feature, every successful check also updates the route gauges of the
+/// calculator metrics.
+#[derive(Debug)]
+pub struct CommuteWatcher {
+    calculator: Arc<WazeRouteCalculator>,
+    routes: Vec<WatchState>,
+    sinks: Vec<Box<dyn AlertSink>>,
+    interval: Duration,
+    history_size: usize,
+}
+
+impl CommuteWatcher {
+    /// The default time between two checks.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(300);
+
+    /// The default number of samples kept per route, a day of checks at the default interval.
+    pub const DEFAULT_HISTORY_SIZE: usize = 288;
+
+    /// Creates a new `CommuteWatcher` without routes nor sinks.
+    ///
+    /// # Arguments
+    ///
+    /// * `calculator` - The calculator checking the routes.
+    ///
+    /// # Returns
+    ///
+    /// A `CommuteWatcher` instance.
+    pub fn new(calculator: impl Into<Arc<WazeRouteCalculator>>) -> Self {
+        CommuteWatcher {
+            calculator: calculator.into(),
+            routes: vec![],
+            sinks: vec![],
+            interval: CommuteWatcher::DEFAULT_INTERVAL,
+            history_size: CommuteWatcher::DEFAULT_HISTORY_SIZE,
+        }
+    }
+
+    /// Adds a route to check.
+    pub fn with_route(mut self, route: WatchedRoute) -> Self {
+        self.routes.push(WatchState {
+            route,
+            history: RouteHistory::new(self.history_size),
+        });
+        self
+    }
+
+    /// Adds a sink delivering the alerts.
+    pub fn with_sink(mut self, sink: impl AlertSink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Sets the time between two checks.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the number of samples kept per route, for the routes added afterwards.
+    pub fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self
+    }
+
+    /// Returns the watched routes.
+    pub fn routes(&self) -> impl Iterator<Item = &WatchedRoute> {
+        self.routes.iter().map(|state| &state.route)
+    }
+
+    /// Returns the history of a route.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the route.
+    ///
+    /// # Returns
+    ///
+    /// The route history, or `None` if no route has this name.
+    pub fn history(&self, name: &str) -> Option<&RouteHistory> {
+        self.routes
+            .iter()
+            .find(|state| state.route.name == name)
+            .map(|state| &state.history)
+    }
+
+    /// Checks every route once, recording the samples and delivering the alerts.
+    ///
+    /// A failed route check or alert delivery is logged and doesn't stop the other ones.
+    ///
+    /// # Returns
+    ///
+    /// The result of each route check, in the routes order.
+    pub fn check(&mut self) -> Vec<RouteCheck> {
+        let mut checks = vec![];
+
+        for state in &mut self.routes {
+            let now = SystemTime::now();
+            let result = self.calculator.route(&state.route.request);
+
+            let alerts = match &result {
+                Ok(summary) => {
+                    let sample = RouteSample::new(now, summary);
+                    debug!("{}: {:?}", state.route.name, sample);
+                    #[cfg(feature = "metrics")]
+                    if let Some(metrics) = self.calculator.metrics() {
+                        metrics.observe_route(&state.route.name, summary);
+                    }
+
+                    let alerts = state.route.alerts(state.history.latest(), &sample);
+                    state.history.push(sample);
+                    alerts
+                }
+                Err(e) => {
+                    warn!("Failed to check {}: {}", state.route.name, e);
+                    vec![]
+                }
+            };
+
+            for alert in &alerts {
+                for sink in &self.sinks {
+                    if let Err(e) = sink.notify(alert) {
+                        error!("Failed to deliver the alert to {:?}: {}", sink, e);
+                    }
+                }
+            }
+
+            checks.push(RouteCheck {
+                route: state.route.name.clone(),
+                result,
+                alerts,
+            });
+        }
+
+        checks
+    }
+
+    /// Checks the routes every interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `iterations` - The number of checks, or `None` to run forever.
+    /// * `on_check` - Called with the results of every check, breaking to stop early.
+    pub fn run(
+        &mut self,
+        iterations: Option<usize>,
+        mut on_check: impl FnMut(Vec<RouteCheck>) -> ControlFlow<()>,
+    ) {
+        let mut iteration = 0;
+
+        while iterations.is_none_or(|iterations| iteration < iterations) {
+            if iteration > 0 {
+                std::thread::sleep(self.interval);
+            }
+
+            if on_check(self.check()).is_break() {
+                break;
+            }
+            iteration += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::Region;
+    use crate::test_fixtures::route_answer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_route_history() {
+        let mut history = RouteHistory::new(2);
+        for minutes in [10, 20, 30] {
+            history.push(RouteSample {
+                time: SystemTime::UNIX_EPOCH,
+                duration: Duration::from_secs(minutes * 60),
+                free_flow_duration: Duration::ZERO,
+                traffic_delay: Duration::ZERO,
+                distance: 0,
+            });
+        }
+
+        pretty_assertions::assert_eq!(
+            history
+                .samples()
+                .map(|x| x.duration.as_secs() / 60)
+                .collect::<Vec<_>>(),
+            vec![20, 30]
+        );
+        pretty_assertions::assert_eq!(
+            history.latest().unwrap().duration,
+            Duration::from_secs(1800)
+        );
+    }
+
+    #[test]
+    fn test_commute_watcher() {
+        let mut waze = mockito::Server::new();
+        let checks = AtomicUsize::new(0);
+        waze.mock("GET", "/RoutingManager/routingRequest")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(move |_| {
+                let minutes = [30, 50, 52, 40, 48][checks.fetch_add(1, Ordering::SeqCst) % 5];
+                route_answer("Ayalon", minutes * 60, 1800, 1000).into_bytes()
+            })
+            .create();
+
+        let mut webhook = mockito::Server::new();
+        let hook = webhook
+            .mock("POST", "/hook")
+            .match_body(mockito::Matcher::PartialJson(
+                json!({ "route": "Home to work" }),
+            ))
+            .expect(3)
+            .create();
+        let broken_hook = webhook
+            .mock("POST", "/broken")
+            .with_status(500)
+            .expect(3)
+            .create();
+
+        let calculator = WazeRouteCalculator::builder()
+            .set_region(Region::US)
+            .set_base_url(&(waze.url() + "/"))
+            .build();
+        let route = WatchedRoute::new(
+            "Home to work",
+            RouteRequest::new((32.08, 34.78), (32.1, 34.8)),
+        )
+        .with_max_duration(Duration::from_secs(45 * 60))
+        .with_max_increase_percent(25.0);
+
+        let mut watcher = CommuteWatcher::new(calculator)
+            .with_history_size(3)
+            .with_route(route)
+            .with_interval(Duration::ZERO)
+            .with_sink(WebhookSink::new(webhook.url() + "/broken").unwrap())
+            .with_sink(WebhookSink::new(webhook.url() + "/hook").unwrap());
+
+        let mut alerts = vec![];
+        let mut messages = vec![];
+        watcher.run(Some(5), |checks| {
+            assert!(checks[0].result.is_ok());
+            alerts.extend(checks[0].alerts.iter().map(|alert| alert.kind));
+            messages.extend(checks[0].alerts.iter().map(|alert| alert.to_string()));
+            ControlFlow::Continue(())
+        });
+
+        hook.assert();
+        broken_hook.assert();
+        pretty_assertions::assert_eq!(
+            alerts,
+            vec![
+                AlertKind::Threshold {
+                    threshold: Duration::from_secs(2700)
+                },
+                AlertKind::Jump {
+                    previous: Duration::from_secs(1800),
+                    percent: 66.66666666666667
+                },
+                AlertKind::Threshold {
+                    threshold: Duration::from_secs(2700)
+                },
+            ]
+        );
+        pretty_assertions::assert_eq!(
+            messages,
+            vec![
+                "Home to work: ETA 50 min is above the 45 min threshold",
+                "Home to work: ETA jumped 67% from 30 min to 50 min",
+                "Home to work: ETA 48 min is above the 45 min threshold",
+            ]
+        );
+        pretty_assertions::assert_eq!(watcher.history("Home to work").unwrap().len(), 3);
+        assert!(watcher.history("Work to home").is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_sink() {
+        let log = std::env::temp_dir().join(format!("waze-rs-alerts-{}.log", std::process::id()));
+        let alert = Alert {
+            route: "Home to work".to_string(),
+            kind: AlertKind::Threshold {
+                threshold: Duration::from_secs(2700),
+            },
+            sample: RouteSample {
+                time: SystemTime::UNIX_EPOCH,
+                duration: Duration::from_secs(3000),
+                free_flow_duration: Duration::from_secs(1800),
+                traffic_delay: Duration::from_secs(1200),
+                distance: 1000,
+            },
+        };
+
+        let sink = CommandSink::shell(&format!(
+            "echo \"$WAZE_ALERT_ROUTE: $WAZE_ALERT_MESSAGE\" >> '{0}' && cat >> '{0}'",
+            log.display()
+        ));
+        sink.notify(&alert).unwrap();
+
+        let text = std::fs::read_to_string(&log).unwrap();
+        let (message, json) = text.split_once('\n').unwrap();
+        pretty_assertions::assert_eq!(
+            message,
+            "Home to work: Home to work: ETA 50 min is above the 45 min threshold"
+        );
+        pretty_assertions::assert_eq!(
+            serde_json::from_str::<Value>(json).unwrap(),
+            alert.to_json()
+        );
+        std::fs::remove_file(&log).unwrap();
+
+        CommandSink::shell("exit 0").notify(&alert).unwrap();
+        let error = CommandSink::shell("exit 3").notify(&alert).unwrap_err();
+        assert!(matches!(
+            error,
+            WazeRouteCalculatorError::NotificationError(_)
+        ));
+    }
+}
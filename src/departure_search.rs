@@ -0,0 +1,418 @@
+use crate::helpers::DepartureTime;
+use crate::waze_route_calculator::{RouteRequest, RouteSummary, WazeRouteCalculatorError};
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+/// A range of departure times, sampled at a fixed step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepartureWindow {
+    /// The first departure time.
+    pub start: DepartureTime,
+
+    /// The last departure time, included.
+    pub end: DepartureTime,
+
+    /// The time between two samples, rounded down to whole minutes.
+    pub step: Duration,
+}
+
+impl DepartureWindow {
+    /// Creates a new `DepartureWindow`.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first departure time.
+    /// * `end` - The last departure time, included.
+    /// * `step` - The time between two samples, at least one minute.
+    ///
+    /// # Returns
+    ///
+    /// A `DepartureWindow` instance.
+    pub fn new(
+        start: impl Into<DepartureTime>,
+        end: impl Into<DepartureTime>,
+        step: Duration,
+    ) -> Self {
+        DepartureWindow {
+            start: start.into(),
+            end: end.into(),
+            step,
+        }
+    }
+
+    /// Returns the sampled departures as minute offsets, the unit of the Waze `at` parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    ///
+    /// The departure offsets from `now`, in minutes.
+    pub fn offsets(&self, now: SystemTime) -> Vec<i64> {
+        let start = self.start.to_minutes_offset(now);
+        let end = self.end.to_minutes_offset(now);
+        let step = (self.step.as_secs() / 60).max(1) as usize;
+
+        (start..=end).step_by(step).collect()
+    }
+}
+
+/// The routes of a `DepartureWindow`, one per sampled departure time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepartureSamples {
+    /// The routes, in departure order.
+    pub samples: Vec<RouteSummary>,
+}
+
+impl DepartureSamples {
+    /// Returns the route with the shortest realtime duration, the earliest one on ties.
+    pub fn fastest(&self) -> Option<&RouteSummary> {
+        self.samples.iter().min_by_key(|route| route.duration)
+    }
+
+    /// Returns the realtime duration of each departure time.
+    pub fn durations(&self) -> Vec<(SystemTime, Duration)> {
+        self.samples
+            .iter()
+            .map(|route| (route.departure_time, route.duration))
+            .collect()
+    }
+}
+
+/// The search of the latest departure arriving by a deadline.
+///
+/// The earliest departure is probed first: if it is late, no departure is on time. Then it probes
+/// the deadline minus the last route duration until a departure is late, and bisects between the
+/// latest departure on time and the earliest late one, to the minute.
+#[derive(Debug)]
+pub(crate) struct ArriveBySearch {
+    arrive_by: SystemTime,
+    next: Option<i64>,
+    on_time: Option<(i64, RouteSummary)>,
+    late: Option<i64>,
+    probes: usize,
+}
+
+impl ArriveBySearch {
+    /// The maximum number of routes requested by a search.
+    const MAX_PROBES: usize = 16;
+
+    /// Creates a new search.
+    ///
+    /// # Arguments
+    ///
+    /// * `earliest` - The earliest departure, in minutes from now.
+    /// * `arrive_by` - The arrival deadline.
+    pub(crate) fn new(earliest: i64, arrive_by: SystemTime) -> Self {
+        ArriveBySearch {
+            arrive_by,
+            next: Some(earliest),
+            on_time: None,
+            late: None,
+            probes: 0,
+        }
+    }
+
+    /// Returns the next departure to probe, in minutes from now, or `None` once the search is over.
+    pub(crate) fn next_departure(&self) -> Option<i64> {
+        if self.probes >= ArriveBySearch::MAX_PROBES {
+            return None;
+        }
+        self.next
+    }
+
+    /// Records the route of a probed departure and picks the next one.
+    pub(crate) fn record(&mut self, departure: i64, route: RouteSummary) {
+        self.probes += 1;
+
+        let slack_minutes = match self.arrive_by.duration_since(route.arrival_time) {
+            Ok(early) => (early.as_secs() / 60) as i64,
+            Err(late) => -(late.duration().as_secs().div_ceil(60) as i64),
+        };
+        debug!(
+            "Departure in {} minutes: {} minutes of slack",
+            departure, slack_minutes
+        );
+
+        if route.arrival_time <= self.arrive_by {
+            self.on_time = Some((departure, route));
+        } else {
+            self.late = Some(self.late.map_or(departure, |late| late.min(departure)));
+        }
+
+        self.next = match (&self.on_time, self.late) {
+            (None, _) => None,
+            (Some((on_time, _)), Some(late)) => {
+                (late - on_time > 1).then(|| on_time + (late - on_time) / 2)
+            }
+            (Some((on_time, _)), None) => {
+                let guess = departure + slack_minutes;
+                (guess > *on_time).then_some(guess)
+            }
+        };
+    }
+
+    /// Returns the route of the latest departure on time, if there is one.
+    pub(crate) fn into_route(self) -> Option<RouteSummary> {
+        self.on_time.map(|(_, route)| route)
+    }
+}
+
+/// Returns a copy of a route request, departing in the given number of minutes.
+fn departing_in(request: &RouteRequest, minutes: i64) -> RouteRequest {
+    request
+        .clone()
+        .with_departure_time(DepartureTime::InMinutes(minutes))
+}
+
+#[cfg(feature = "blocking")]
+impl crate::waze_route_calculator::WazeRouteCalculator {
+    /// Calculates the route of a request for every departure time of a window.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, its departure time being ignored.
+    /// * `window` - The departure times to sample.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `DepartureSamples` with a route per departure time, or an error.
+    pub fn calculate_departures(
+        &self,
+        request: &RouteRequest,
+        window: &DepartureWindow,
+    ) -> Result<DepartureSamples, WazeRouteCalculatorError> {
+        let samples = window
+            .offsets(SystemTime::now())
+            .into_iter()
+            .map(|minutes| self.route(&departing_in(request, minutes)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(DepartureSamples { samples })
+    }
+
+    /// Finds the latest departure arriving by a deadline, to the minute.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, its departure time, or the calculator one, being the earliest departure.
+    /// * `arrive_by` - The arrival deadline.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the route of the latest departure on time, `None` if even the earliest
+    /// departure is late, or an error.
+    pub fn latest_departure(
+        &self,
+        request: &RouteRequest,
+        arrive_by: impl Into<SystemTime>,
+    ) -> Result<Option<RouteSummary>, WazeRouteCalculatorError> {
+        let earliest = request
+            .departure_time
+            .unwrap_or(self.departure_time)
+            .to_minutes_offset(SystemTime::now());
+        let mut search = ArriveBySearch::new(earliest, arrive_by.into());
+
+        while let Some(minutes) = search.next_departure() {
+            let route = self.route(&departing_in(request, minutes))?;
+            search.record(minutes, route);
+        }
+
+        Ok(search.into_route())
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::async_waze_route_calculator::AsyncWazeRouteCalculator {
+    /// Calculates the route of a request for every departure time of a window.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, its departure time being ignored.
+    /// * `window` - The departure times to sample.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `DepartureSamples` with a route per departure time, or an error.
+    pub async fn calculate_departures(
+        &self,
+        request: &RouteRequest,
+        window: &DepartureWindow,
+    ) -> Result<DepartureSamples, WazeRouteCalculatorError> {
+        let mut samples = vec![];
+        for minutes in window.offsets(SystemTime::now()) {
+            samples.push(self.route(&departing_in(request, minutes)).await?);
+        }
+
+        Ok(DepartureSamples { samples })
+    }
+
+    /// Finds the latest departure arriving by a deadline, to the minute.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, its departure time, or the calculator one, being the earliest departure.
+    /// * `arrive_by` - The arrival deadline.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the route of the latest departure on time, `None` if even the earliest
+    /// departure is late, or an error.
+    pub async fn latest_departure(
+        &self,
+        request: &RouteRequest,
+        arrive_by: impl Into<SystemTime>,
+    ) -> Result<Option<RouteSummary>, WazeRouteCalculatorError> {
+        let earliest = request
+            .departure_time
+            .unwrap_or(self.departure_time())
+            .to_minutes_offset(SystemTime::now());
+        let mut search = ArriveBySearch::new(earliest, arrive_by.into());
+
+        while let Some(minutes) = search.next_departure() {
+            let route = self.route(&departing_in(request, minutes)).await?;
+            search.record(minutes, route);
+        }
+
+        Ok(search.into_route())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::route_answer;
+    use crate::transport::{Transport, TransportRequest, TransportResponse};
+    use crate::waze_route_calculator::WazeRouteCalculator;
+
+    /// Answers with a route of about 30 minutes, fastest when departing in 3 hours, and taking an
+    /// hour when departing between 60 and 120 minutes from now.
+    #[derive(Debug)]
+    struct RushHourTransport;
+
+    impl RushHourTransport {
+        fn answer(
+            &self,
+            request: &TransportRequest,
+        ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+            let at = request
+                .query
+                .iter()
+                .find(|(name, _)| name == "at")
+                .map(|(_, value)| value.parse::<i64>().unwrap())
+                .unwrap();
+            let minutes = if (60..=120).contains(&at) {
+                60
+            } else {
+                30 + (at - 180).abs() / 30
+            };
+
+            Ok(TransportResponse::new(
+                200,
+                route_answer("", minutes as u64 * 60, 1800, 1000),
+            ))
+        }
+    }
+
+    impl Transport for RushHourTransport {
+        fn send(
+            &self,
+            request: &TransportRequest,
+        ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+            self.answer(request)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl crate::transport::AsyncTransport for RushHourTransport {
+        fn send<'a>(
+            &'a self,
+            request: &'a TransportRequest,
+        ) -> crate::transport::TransportFuture<'a> {
+            Box::pin(std::future::ready(self.answer(request)))
+        }
+    }
+
+    fn departs_in(route: &RouteSummary, now: SystemTime) -> u64 {
+        route.departure_time.duration_since(now).unwrap().as_secs() / 60
+    }
+
+    #[test]
+    fn test_departure_window() {
+        let now = SystemTime::now();
+        let window = DepartureWindow::new(
+            now + Duration::from_secs(3600),
+            DepartureTime::InMinutes(120),
+            Duration::from_secs(900),
+        );
+
+        pretty_assertions::assert_eq!(window.offsets(now), vec![60, 75, 90, 105, 120]);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_calculate_departures() {
+        let calculator = WazeRouteCalculator::builder()
+            .set_transport(RushHourTransport)
+            .build();
+        let request = RouteRequest::new((32.08, 34.78), (31.768, 35.214));
+        let window = DepartureWindow::new(
+            DepartureTime::Now,
+            DepartureTime::InMinutes(240),
+            Duration::from_secs(1800),
+        );
+
+        let now = SystemTime::now();
+        let departures = calculator.calculate_departures(&request, &window).unwrap();
+        pretty_assertions::assert_eq!(
+            departures
+                .durations()
+                .iter()
+                .map(|(_, duration)| duration.as_secs() / 60)
+                .collect::<Vec<_>>(),
+            vec![36, 35, 60, 60, 60, 31, 30, 31, 32]
+        );
+        pretty_assertions::assert_eq!(departs_in(departures.fastest().unwrap(), now), 180);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_latest_departure() {
+        let calculator = WazeRouteCalculator::builder()
+            .set_transport(RushHourTransport)
+            .build();
+        let request = RouteRequest::new((32.08, 34.78), (31.768, 35.214));
+
+        let now = SystemTime::now();
+        let route = calculator
+            .latest_departure(&request, now + Duration::from_secs(150 * 60 + 30))
+            .unwrap()
+            .unwrap();
+        pretty_assertions::assert_eq!(departs_in(&route, now), 90);
+        pretty_assertions::assert_eq!(route.duration, Duration::from_secs(3600));
+
+        let route = calculator
+            .latest_departure(&request, now + Duration::from_secs(20 * 60))
+            .unwrap();
+        assert!(route.is_none());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "async")]
+    async fn test_async_latest_departure() {
+        let calculator = WazeRouteCalculator::builder()
+            .set_async_transport(RushHourTransport)
+            .build_async();
+        let request = RouteRequest::new((32.08, 34.78), (31.768, 35.214))
+            .with_departure_time(DepartureTime::InMinutes(30));
+
+        let now = SystemTime::now();
+        let route = calculator
+            .latest_departure(&request, now + Duration::from_secs(150 * 60 + 30))
+            .await
+            .unwrap()
+            .unwrap();
+        pretty_assertions::assert_eq!(departs_in(&route, now), 90);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::helpers::{write_file_atomically, Region};
+use crate::waze_route_calculator::WazeRouteCalculatorError;
+use crate::waze_structs::Coordinates;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, warn};
+
+/// A cached geocoding answer.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct CacheEntry {
+    coords: Coordinates,
+    inserted: SystemTime,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    clock: u64,
+}
+
+/// A geocoding cache in front of `address_to_coords`, with a TTL, an in-memory LRU and an
+/// optional JSON file so the cache survives process restarts.
+///
+/// Entries are keyed by the normalized address, the region and the language.
+#[derive(Debug)]
+pub struct GeocodeCache {
+    state: Mutex<CacheState>,
+    capacity: usize,
+    ttl: Duration,
+    path: Option<PathBuf>,
+}
+
+impl GeocodeCache {
+    /// Creates a new in-memory `GeocodeCache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of addresses to keep, least recently used ones are evicted first.
+    /// * `ttl` - How long a cached answer stays valid.
+    ///
+    /// # Returns
+    ///
+    /// A `GeocodeCache` instance.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        GeocodeCache {
+            state: Mutex::new(CacheState::default()),
+            capacity,
+            ttl,
+            path: None,
+        }
+    }
+
+    /// Creates a new `GeocodeCache` persisted to a JSON file, loading the file if it exists.
+    ///
+    /// A damaged file is logged and ignored, the cache starting empty and overwriting it.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of addresses to keep, least recently used ones are evicted first.
+    /// * `ttl` - How long a cached answer stays valid.
+    /// * `path` - The JSON file to load from and save to.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `GeocodeCache` instance or an error if the file can't be read.
+    pub fn with_file(
+        capacity: usize,
+        ttl: Duration,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, WazeRouteCalculatorError> {
+        let path = path.as_ref().to_path_buf();
+        let mut state = CacheState::default();
+
+        if path.exists() {
+            match serde_json::from_str(&std::fs::read_to_string(&path)?) {
+                Ok(entries) => {
+                    state.entries = entries;
+                    debug!("Loaded {} geocode cache entries", state.entries.len());
+                }
+                Err(e) => warn!(
+                    "Ignoring the damaged geocode cache file {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        let cache = GeocodeCache {
+            state: Mutex::new(state),
+            capacity,
+            ttl,
+            path: Some(path),
+        };
+        cache.evict(&mut cache.state.lock().unwrap());
+
+        Ok(cache)
+    }
+
+    /// Builds the cache key of an address.
+    fn key(address: &str, region: Region, language: &str) -> String {
+        let address = address
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+
+        format!("{:?}|{}|{}", region, language, address)
+    }
+
+    /// Looks up the coordinates of an address.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to look up.
+    /// * `region` - The region the address was geocoded in.
+    /// * `language` - The language the address was geocoded with.
+    ///
+    /// # Returns
+    ///
+    /// The cached coordinates, or `None` if the address is missing or expired.
+    pub fn get(&self, address: &str, region: Region, language: &str) -> Option<Coordinates> {
+        let key = GeocodeCache::key(address, region, language);
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        let entry = state.entries.get_mut(&key)?;
+        if entry.inserted.elapsed().unwrap_or_default() > self.ttl {
+            debug!("Geocode cache entry expired: {}", key);
+            state.entries.remove(&key);
+            return None;
+        }
+
+        entry.last_used = clock;
+        debug!("Geocode cache hit: {}", key);
+        Some(entry.coords)
+    }
+
+    /// Stores the coordinates of an address, saving the cache file if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The geocoded address.
+    /// * `region` - The region the address was geocoded in.
+    /// * `language` - The language the address was geocoded with.
+    /// * `coords` - The coordinates of the address.
+    pub fn insert(&self, address: &str, region: Region, language: &str, coords: Coordinates) {
+        let key = GeocodeCache::key(address, region, language);
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+
+        let entry = CacheEntry {
+            coords,
+            inserted: SystemTime::now(),
+            last_used: state.clock,
+        };
+        state.entries.insert(key, entry);
+        self.evict(&mut state);
+
+        if let Err(e) = self.save(&state) {
+            error!("Failed to save the geocode cache: {}", e);
+        }
+    }
+
+    /// Removes every cached address.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+
+        if let Err(e) = self.save(&state) {
+            error!("Failed to save the geocode cache: {}", e);
+        }
+    }
+
+    /// Returns the number of cached addresses.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Checks if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the expired entries, then the least recently used ones above capacity.
+    fn evict(&self, state: &mut CacheState) {
+        state
+            .entries
+            .retain(|_, entry| entry.inserted.elapsed().unwrap_or_default() <= self.ttl);
+
+        while state.entries.len() > self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            state.entries.remove(&oldest);
+        }
+    }
+
+    /// Writes the cache to its file, if it has one.
+    fn save(&self, state: &CacheState) -> Result<(), WazeRouteCalculatorError> {
+        if let Some(path) = &self.path {
+            write_file_atomically(path, &serde_json::to_string(&state.entries)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geocode_cache_lru_and_ttl() {
+        let cache = GeocodeCache::new(2, Duration::from_secs(60));
+        let coords = Coordinates::new(32.08, 34.78);
+
+        cache.insert("Tel Aviv", Region::IL, "eng", coords);
+        cache.insert("Haifa", Region::IL, "eng", Coordinates::new(32.79, 34.99));
+
+        pretty_assertions::assert_eq!(cache.get("  tel   AVIV ", Region::IL, "eng"), Some(coords));
+        pretty_assertions::assert_eq!(cache.get("Tel Aviv", Region::EU, "eng"), None);
+
+        cache.insert("Eilat", Region::IL, "eng", Coordinates::new(29.55, 34.95));
+        pretty_assertions::assert_eq!(cache.len(), 2);
+        pretty_assertions::assert_eq!(cache.get("Haifa", Region::IL, "eng"), None);
+        pretty_assertions::assert_eq!(cache.get("Tel Aviv", Region::IL, "eng"), Some(coords));
+
+        let expired = GeocodeCache::new(2, Duration::ZERO);
+        expired.insert("Tel Aviv", Region::IL, "eng", coords);
+        std::thread::sleep(Duration::from_millis(5));
+        pretty_assertions::assert_eq!(expired.get("Tel Aviv", Region::IL, "eng"), None);
+    }
+
+    #[test]
+    fn test_geocode_cache_file() {
+        let path =
+            std::env::temp_dir().join(format!("waze-rs-geocode-cache-{}.json", std::process::id()));
+        let coords = Coordinates::new(32.08, 34.78);
+
+        let cache = GeocodeCache::with_file(10, Duration::from_secs(60), &path).unwrap();
+        cache.insert("Tel Aviv", Region::IL, "eng", coords);
+        drop(cache);
+
+        let cache = GeocodeCache::with_file(10, Duration::from_secs(60), &path).unwrap();
+        pretty_assertions::assert_eq!(cache.get("Tel Aviv", Region::IL, "eng"), Some(coords));
+        drop(cache);
+
+        std::fs::write(&path, r#"{"IL|eng|tel aviv": {"coords": {"lat"#).unwrap();
+        let cache = GeocodeCache::with_file(10, Duration::from_secs(60), &path).unwrap();
+        assert!(cache.is_empty());
+        cache.insert("Haifa", Region::IL, "eng", coords);
+
+        let cache = GeocodeCache::with_file(10, Duration::from_secs(60), &path).unwrap();
+        pretty_assertions::assert_eq!(cache.get("Haifa", Region::IL, "eng"), Some(coords));
+
+        let leftovers = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.starts_with(path.file_name().unwrap().to_str().unwrap()))
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(leftovers.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,10 @@
-use crate::waze_route_calculator::WazeRouteCalculator;
-use crate::waze_structs::WazeAddressCoordinates;
+use crate::waze_route_calculator::{WazeRouteCalculator, WazeRouteCalculatorError};
+use crate::waze_structs::{Coordinates, WazeAddressCoordinates};
+use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
 
 /// Enum representing the region of the Waze server.
 #[derive(Copy, Clone, Debug)]
@@ -17,6 +22,21 @@ pub enum Region {
     AU,
 }
 
+impl FromStr for Region {
+    type Err = String;
+
+    /// Parses a region code, e.g. `"IL"`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "US" => Ok(Region::US),
+            "EU" => Ok(Region::EU),
+            "IL" => Ok(Region::IL),
+            "AU" => Ok(Region::AU),
+            _ => Err(format!("Unknown region: {} (expected US, EU, IL or AU)", s)),
+        }
+    }
+}
+
 /// Enum representing the vehicle type.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum VehicleType {
@@ -43,10 +63,287 @@ impl VehicleType {
         }
     }
 }
+
+impl FromStr for VehicleType {
+    type Err = String;
+
+    /// Parses a vehicle type, e.g. `"taxi"`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "CAR" => Ok(VehicleType::CAR),
+            "TAXI" => Ok(VehicleType::TAXI),
+            "MOTORCYCLE" => Ok(VehicleType::MOTORCYCLE),
+            _ => Err(format!(
+                "Unknown vehicle type: {} (expected CAR, TAXI or MOTORCYCLE)",
+                s
+            )),
+        }
+    }
+}
+
+/// Enum representing the Waze server a request is sent to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Endpoint {
+    /// The address search server
+    Search,
+
+    /// The routing server
+    Routing,
+}
+
+/// Calculates the great-circle distance between two coordinates.
+///
+/// # Arguments
+/// * `a` - The first coordinates.
+/// * `b` - The second coordinates.
+///
+/// # Returns
+/// * The distance in meters.
+pub fn haversine_distance(a: &Coordinates, b: &Coordinates) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+
+    let d_lat = (b.latitude - a.latitude).to_radians();
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2)
+        + a.latitude.to_radians().cos()
+            * b.latitude.to_radians().cos()
+            * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+/// Parses a coordinate string, in decimal degrees or in degrees, minutes and seconds (DMS).
+///
+/// Accepts e.g. `"32.08,34.78"`, `"32.08 34.78"`, `"32°04'48\"N 34°46'48\"E"` or `"N32 4.8 E34 46.8"`.
+/// Plain numbers without a hemisphere or a degree sign need a decimal point, so that
+/// addresses like `"12 34"` are not mistaken for coordinates.
+///
+/// # Arguments
+/// * `text` - The text to parse.
+///
+/// # Returns
+/// * The parsed `Coordinates`, or `None` if the text is not a coordinate pair.
+pub fn parse_coordinates(text: &str) -> Option<Coordinates> {
+    const HEMISPHERES: &str = "NSEW";
+    const MARKS: &str = "°º'\"′″";
+
+    let text = text.trim();
+    if text.is_empty()
+        || !text.chars().all(|c| {
+            c.is_ascii_digit()
+                || c.is_whitespace()
+                || ".,+-".contains(c)
+                || MARKS.contains(c)
+                || HEMISPHERES.contains(c.to_ascii_uppercase())
+        })
+    {
+        return None;
+    }
+
+    let prefix = text.starts_with(|c: char| HEMISPHERES.contains(c.to_ascii_uppercase()));
+    let mut components: Vec<(String, Option<char>)> = vec![];
+    let mut current = String::new();
+    let mut pending = None;
+
+    for c in text.chars() {
+        let upper = c.to_ascii_uppercase();
+        if HEMISPHERES.contains(upper) {
+            if prefix {
+                if !current.trim().is_empty() {
+                    components.push((std::mem::take(&mut current), pending.take()));
+                }
+                pending = Some(upper);
+            } else if current.trim().is_empty() {
+                return None;
+            } else {
+                components.push((std::mem::take(&mut current), Some(upper)));
+            }
+        } else if c == ',' {
+            if !current.trim().is_empty() {
+                components.push((std::mem::take(&mut current), pending.take()));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        components.push((current, pending));
+    }
+
+    if components.len() == 1 && components[0].1.is_none() {
+        components = components[0]
+            .0
+            .split_whitespace()
+            .map(|x| (x.to_string(), None))
+            .collect();
+    }
+
+    if components.len() != 2 {
+        return None;
+    }
+
+    let plain = components
+        .iter()
+        .all(|(x, hemisphere)| hemisphere.is_none() && !x.contains(|c| MARKS.contains(c)));
+    if plain && !components.iter().any(|(x, _)| x.contains('.')) {
+        return None;
+    }
+
+    let to_degrees = |(text, hemisphere): &(String, Option<char>)| -> Option<f64> {
+        let numbers = text
+            .split(|c: char| c.is_whitespace() || MARKS.contains(c))
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse::<f64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        let (degrees, minutes, seconds) = match numbers[..] {
+            [degrees] => (degrees, 0.0, 0.0),
+            [degrees, minutes] => (degrees, minutes, 0.0),
+            [degrees, minutes, seconds] => (degrees, minutes, seconds),
+            _ => return None,
+        };
+
+        if !(0.0..60.0).contains(&minutes) || !(0.0..60.0).contains(&seconds) {
+            return None;
+        }
+
+        let value = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+        let negative = degrees.is_sign_negative() || matches!(hemisphere, Some('S' | 'W'));
+        Some(if negative { -value } else { value })
+    };
+
+    let is_longitude = |hemisphere: Option<char>| matches!(hemisphere, Some('E' | 'W'));
+    let (lat, lon) = match (components[0].1, components[1].1) {
+        (first, second) if is_longitude(first) && !is_longitude(second) => {
+            (to_degrees(&components[1])?, to_degrees(&components[0])?)
+        }
+        (first, second) if !is_longitude(first) && (second.is_none() || is_longitude(second)) => {
+            (to_degrees(&components[0])?, to_degrees(&components[1])?)
+        }
+        _ => return None,
+    };
+
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return None;
+    }
+
+    Some(Coordinates::new(lat, lon))
+}
+
+/// Writes a file through a temporary file in the same directory renamed over it, so that a crash
+/// or a concurrent writer never leaves a truncated file behind.
+///
+/// # Arguments
+/// * `path` - The file to write.
+/// * `contents` - The new contents of the file.
+pub(crate) fn write_file_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(file_name);
+
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })
+}
+
+/// Enum representing when the route starts.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DepartureTime {
+    /// Leave now
+    #[default]
+    Now,
+
+    /// Leave in the given number of minutes (negative for the past)
+    InMinutes(i64),
+
+    /// Leave at the given time
+    At(SystemTime),
+}
+
+impl DepartureTime {
+    /// Converts the departure time to the minute offset used by the Waze `at` parameter.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// * The departure offset from `now` in whole minutes, rounded to the nearest minute.
+    pub fn to_minutes_offset(&self, now: SystemTime) -> i64 {
+        match self {
+            DepartureTime::Now => 0,
+            DepartureTime::InMinutes(minutes) => *minutes,
+            DepartureTime::At(time) => match time.duration_since(now) {
+                Ok(ahead) => ((ahead.as_secs() + 30) / 60) as i64,
+                Err(behind) => -(((behind.duration().as_secs() + 30) / 60) as i64),
+            },
+        }
+    }
+
+    /// Converts the departure time to an absolute time.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// * The departure time as a `SystemTime`, or an `InvalidDepartureTime` error if the offset
+    ///   is out of the `SystemTime` range.
+    pub fn to_system_time(&self, now: SystemTime) -> Result<SystemTime, WazeRouteCalculatorError> {
+        let minutes = match self {
+            DepartureTime::Now => return Ok(now),
+            DepartureTime::At(time) => return Ok(*time),
+            DepartureTime::InMinutes(minutes) => *minutes,
+        };
+
+        let time = minutes
+            .unsigned_abs()
+            .checked_mul(60)
+            .map(Duration::from_secs)
+            .and_then(|offset| {
+                if minutes >= 0 {
+                    now.checked_add(offset)
+                } else {
+                    now.checked_sub(offset)
+                }
+            });
+
+        time.ok_or_else(|| {
+            WazeRouteCalculatorError::InvalidDepartureTime(format!(
+                "{} minutes from now is out of range",
+                minutes
+            ))
+        })
+    }
+}
+
+impl From<SystemTime> for DepartureTime {
+    /// Converts a `SystemTime` to a `DepartureTime::At`.
+    fn from(time: SystemTime) -> Self {
+        DepartureTime::At(time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for DepartureTime {
+    /// Converts a `chrono::DateTime` to a `DepartureTime::At`.
+    fn from(time: chrono::DateTime<Tz>) -> Self {
+        DepartureTime::At(time.into())
+    }
+}
+
 impl WazeRouteCalculator {
     /// Base Waze URL
     pub const WAZE_URL: &'static str = "https://www.waze.com/";
 
+    /// Language of the search server answers
+    pub(crate) const LANGUAGE: &'static str = "eng";
+
     /// Base Coordinates for each region
     pub(crate) const BASE_COORDS: [(Region, WazeAddressCoordinates); 4] = [
         (
@@ -0,0 +1,155 @@
+use reqwest::{Certificate, Proxy};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::debug;
+
+/// The settings of the HTTP client used to reach the Waze servers.
+#[derive(Clone, Debug, Default)]
+pub struct HttpClientConfig {
+    /// The maximum time to establish a connection.
+    pub connect_timeout: Option<Duration>,
+
+    /// The maximum time to wait for data from the server.
+    ///
+    /// The blocking client has no read timeout, so it bounds the whole request there.
+    pub read_timeout: Option<Duration>,
+
+    /// The HTTP, HTTPS or SOCKS proxy to send the requests through.
+    ///
+    /// SOCKS proxies need the `socks` feature.
+    pub proxy: Option<Proxy>,
+
+    /// Extra root certificates to trust, e.g. the CA of a corporate proxy.
+    pub root_certificates: Vec<Certificate>,
+}
+
+impl HttpClientConfig {
+    /// Builds a blocking client with these settings.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the client or an error if the TLS backend or the proxy can't be set up.
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(&self) -> Result<reqwest::blocking::Client, reqwest::Error> {
+        let mut builder = reqwest::blocking::Client::builder();
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+
+        builder.build()
+    }
+
+    /// Builds an async client with these settings.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the client or an error if the TLS backend or the proxy can't be set up.
+    pub fn build_async(&self) -> Result<reqwest::Client, reqwest::Error> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+
+        builder.build()
+    }
+}
+
+/// The HTTP clients of a calculator, built on first use and then reused for connection pooling.
+#[derive(Debug, Default)]
+pub(crate) struct HttpClients {
+    config: HttpClientConfig,
+    #[cfg(feature = "blocking")]
+    blocking: OnceLock<reqwest::blocking::Client>,
+    #[cfg(feature = "async")]
+    non_blocking: OnceLock<reqwest::Client>,
+}
+
+impl HttpClients {
+    /// Creates the clients holder, with optional clients injected by the user.
+    pub(crate) fn new(
+        config: HttpClientConfig,
+        #[cfg(feature = "blocking")] blocking: Option<reqwest::blocking::Client>,
+        #[cfg(feature = "async")] non_blocking: Option<reqwest::Client>,
+    ) -> Self {
+        HttpClients {
+            config,
+            #[cfg(feature = "blocking")]
+            blocking: blocking.map(OnceLock::from).unwrap_or_default(),
+            #[cfg(feature = "async")]
+            non_blocking: non_blocking.map(OnceLock::from).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the blocking client, building it on first use.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn blocking(&self) -> Result<&reqwest::blocking::Client, reqwest::Error> {
+        if let Some(client) = self.blocking.get() {
+            return Ok(client);
+        }
+
+        debug!("Building blocking HTTP client: {:?}", self.config);
+        let client = self.config.build_blocking()?;
+        Ok(self.blocking.get_or_init(|| client))
+    }
+
+    /// Returns the async client, building it on first use.
+    #[cfg(feature = "async")]
+    pub(crate) fn non_blocking(&self) -> Result<&reqwest::Client, reqwest::Error> {
+        if let Some(client) = self.non_blocking.get() {
+            return Ok(client);
+        }
+
+        debug!("Building async HTTP client: {:?}", self.config);
+        let client = self.config.build_async()?;
+        Ok(self.non_blocking.get_or_init(|| client))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client_config() {
+        let config = HttpClientConfig {
+            connect_timeout: Some(Duration::from_secs(2)),
+            read_timeout: Some(Duration::from_secs(5)),
+            proxy: Some(Proxy::all("http://127.0.0.1:3128").unwrap()),
+            root_certificates: vec![],
+        };
+
+        assert!(config.build_async().is_ok());
+
+        #[cfg(feature = "blocking")]
+        {
+            let clients = HttpClients::new(
+                config,
+                None,
+                #[cfg(feature = "async")]
+                None,
+            );
+            let client = clients.blocking().unwrap();
+            assert!(std::ptr::eq(client, clients.blocking().unwrap()));
+        }
+    }
+}
@@ -4,11 +4,65 @@
 //!
 //! Uses serde and reqwest to make requests to Waze API.
 
+#[cfg(not(any(feature = "blocking", feature = "async")))]
+compile_error!("waze-rs needs the `blocking` or the `async` feature to talk to the Waze servers");
+
 /// The main struct for the Waze API.
 pub mod waze_route_calculator;
 
+/// The async twin of the Waze route calculator.
+#[cfg(feature = "async")]
+pub mod async_waze_route_calculator;
+
 /// Structs for the Waze API.
 pub mod waze_structs;
 
 /// Helper functions and structs for the Waze API.
 pub mod helpers;
+
+/// Route geometry exporters (GeoJSON, GPX and KML).
+pub mod route_export;
+
+/// Turn-by-turn instructions of a route.
+pub mod route_instructions;
+
+/// Geocoding cache with TTL and persistent backend.
+pub mod geocode_cache;
+
+/// Routing answer cache keyed by coordinates, options and time bucket.
+pub mod route_cache;
+
+/// Retry policy with exponential backoff and jitter.
+pub mod retry;
+
+/// Immutable route query with per-request option overrides.
+pub mod route_request;
+
+/// Typed routing options.
+pub mod route_options;
+
+/// Distance and duration matrix between many origins and destinations.
+pub mod route_matrix;
+
+/// Best departure time search over a time window, and latest departure to arrive by a deadline.
+pub mod departure_search;
+
+/// Commute watcher recalculating routes on a schedule and firing alerts.
+#[cfg(feature = "blocking")]
+pub mod commute_watcher;
+
+/// Prometheus metrics of the clients and of the watched routes, served on `/metrics`.
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+/// Client-side rate limiter with separate search and routing budgets.
+pub mod rate_limit;
+
+/// Configuration of the reusable HTTP client (timeouts, proxy and TLS).
+pub mod http_client;
+
+/// Pluggable transport for the HTTP calls, with in-memory and record/replay implementations.
+pub mod transport;
+
+#[cfg(test)]
+mod test_fixtures;
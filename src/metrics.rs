@@ -0,0 +1,359 @@
+use crate::helpers::Endpoint;
+use crate::waze_route_calculator::{RouteSummary, WazeRouteCalculatorError};
+use prometheus::{
+    GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+/// Prometheus metrics of the Waze clients and of the watched routes.
+///
+/// Set on a calculator with `set_metrics()` to count its requests, errors and cache hits, and
+/// every `CommuteWatcher` check of that calculator updates the route gauges.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    errors: IntCounterVec,
+    cache_hits: IntCounterVec,
+    cache_misses: IntCounterVec,
+    route_duration: GaugeVec,
+    route_free_flow_duration: GaugeVec,
+    route_traffic_delay: GaugeVec,
+    route_distance: GaugeVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    /// The content type of the Prometheus text format.
+    pub const CONTENT_TYPE: &'static str = "text/plain; version=0.0.4; charset=utf-8";
+
+    /// Creates a new `Metrics` with its own registry.
+    ///
+    /// # Returns
+    ///
+    /// A `Metrics` instance with all the counters at zero.
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+        let gauge = |name: &str, help: &str| {
+            let gauge = GaugeVec::new(Opts::new(name, help), &["route"]).unwrap();
+            registry.register(Box::new(gauge.clone())).unwrap();
+            gauge
+        };
+
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "waze_request_duration_seconds",
+                "Latency of the requests to the Waze servers",
+            ),
+            &["endpoint"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+
+        Metrics {
+            requests: counter(
+                "waze_requests_total",
+                "Requests sent to the Waze servers, retries included",
+                &["endpoint"],
+            ),
+            request_duration,
+            errors: counter(
+                "waze_errors_total",
+                "Failed Waze calls, by error variant",
+                &["endpoint", "error"],
+            ),
+            cache_hits: counter(
+                "waze_cache_hits_total",
+                "Answers read from a cache",
+                &["cache"],
+            ),
+            cache_misses: counter(
+                "waze_cache_misses_total",
+                "Cache lookups falling back to the Waze servers",
+                &["cache"],
+            ),
+            route_duration: gauge(
+                "waze_route_duration_seconds",
+                "Realtime duration of the last check of a watched route",
+            ),
+            route_free_flow_duration: gauge(
+                "waze_route_free_flow_duration_seconds",
+                "Free-flow duration of the last check of a watched route",
+            ),
+            route_traffic_delay: gauge(
+                "waze_route_traffic_delay_seconds",
+                "Traffic delay of the last check of a watched route",
+            ),
+            route_distance: gauge(
+                "waze_route_distance_meters",
+                "Distance of the last check of a watched route",
+            ),
+            registry,
+        }
+    }
+
+    /// Returns the registry of the metrics, to gather them with other metrics.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Records a request sent to a Waze server.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The server the request was sent to.
+    /// * `elapsed` - The time until the answer or the transport error.
+    pub fn observe_request(&self, endpoint: Endpoint, elapsed: Duration) {
+        let endpoint = endpoint_label(endpoint);
+        self.requests.with_label_values(&[endpoint]).inc();
+        self.request_duration
+            .with_label_values(&[endpoint])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Records a failed Waze call.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The server of the call.
+    /// * `error` - The error of the call.
+    pub fn observe_error(&self, endpoint: Endpoint, error: &WazeRouteCalculatorError) {
+        self.errors
+            .with_label_values(&[endpoint_label(endpoint), error_label(error)])
+            .inc();
+    }
+
+    /// Records a cache lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The name of the cache, `geocode` or `route`.
+    /// * `hit` - Whether the answer was in the cache.
+    pub fn observe_cache(&self, cache: &str, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.with_label_values(&[cache]).inc();
+    }
+
+    /// Sets the gauges of a watched route to its last check.
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - The name of the route.
+    /// * `summary` - The checked route.
+    pub fn observe_route(&self, route: &str, summary: &RouteSummary) {
+        let labels = [route];
+        self.route_duration
+            .with_label_values(&labels)
+            .set(summary.duration.as_secs_f64());
+        self.route_free_flow_duration
+            .with_label_values(&labels)
+            .set(summary.free_flow_duration.as_secs_f64());
+        self.route_traffic_delay
+            .with_label_values(&labels)
+            .set(summary.traffic_delay.as_secs_f64());
+        self.route_distance
+            .with_label_values(&labels)
+            .set(summary.distance as f64);
+    }
+
+    /// Encodes the metrics in the Prometheus text format.
+    ///
+    /// # Returns
+    ///
+    /// The text of a `/metrics` answer.
+    pub fn encode(&self) -> String {
+        let mut text = String::new();
+        if let Err(e) = TextEncoder::new().encode_utf8(&self.registry.gather(), &mut text) {
+            error!("Failed to encode the metrics: {}", e);
+        }
+        text
+    }
+
+    /// Serves the metrics on `GET /metrics` from a background thread, until the process exits.
+    ///
+    /// Each connection is answered on its own thread, so a slow client never delays the others.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `"0.0.0.0:9898"`, port 0 picking a free port.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the address the server listens on, or an error if it cannot bind.
+    pub fn serve(
+        self: Arc<Self>,
+        addr: impl ToSocketAddrs,
+    ) -> Result<SocketAddr, WazeRouteCalculatorError> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("Serving the metrics on http://{}/metrics", local_addr);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        debug!("Metrics connection failed: {}", e);
+                        continue;
+                    }
+                };
+
+                let metrics = self.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = metrics.answer(stream) {
+                        debug!("Metrics request failed: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(local_addr)
+    }
+
+    /// Answers an HTTP request, with the metrics on `GET /metrics` and a 404 otherwise.
+    fn answer(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(5)))?;
+
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            header.clear();
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, content_type, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some("/metrics")) => ("200 OK", Metrics::CONTENT_TYPE, self.encode()),
+            _ => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+        };
+
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+/// Returns the `endpoint` label of a Waze server.
+fn endpoint_label(endpoint: Endpoint) -> &'static str {
+    match endpoint {
+        Endpoint::Search => "search",
+        Endpoint::Routing => "routing",
+    }
+}
+
+/// Returns the `error` label of an error, its variant name.
+fn error_label(error: &WazeRouteCalculatorError) -> &'static str {
+    match error {
+        WazeRouteCalculatorError::FailedToGetRoute => "FailedToGetRoute",
+        WazeRouteCalculatorError::WazeApiError(_) => "WazeApiError",
+        WazeRouteCalculatorError::NoResults(_) => "NoResults",
+        WazeRouteCalculatorError::HttpStatus { .. } => "HttpStatus",
+        WazeRouteCalculatorError::HtmlResponse { .. } => "HtmlResponse",
+        WazeRouteCalculatorError::InvalidResponse(_) => "InvalidResponse",
+        WazeRouteCalculatorError::InvalidDepartureTime(_) => "InvalidDepartureTime",
+        WazeRouteCalculatorError::MissingCoordinates(_) => "MissingCoordinates",
+        WazeRouteCalculatorError::NetworkError(_) => "NetworkError",
+        WazeRouteCalculatorError::SerializationError(_) => "SerializationError",
+        WazeRouteCalculatorError::IoError(_) => "IoError",
+        WazeRouteCalculatorError::TransportError(_) => "TransportError",
+        WazeRouteCalculatorError::NotificationError(_) => "NotificationError",
+        WazeRouteCalculatorError::UnknownError => "UnknownError",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commute_watcher::{CommuteWatcher, WatchedRoute};
+    use crate::route_cache::RouteCache;
+    use crate::test_fixtures::route_answer;
+    use crate::transport::{FakeTransport, TransportResponse};
+    use crate::waze_route_calculator::{RouteRequest, WazeRouteCalculator};
+
+    #[test]
+    fn test_metrics() {
+        let transport = FakeTransport::new()
+            .with_response("SearchServer", TransportResponse::new(503, ""))
+            .with_response(
+                "routingRequest",
+                TransportResponse::new(200, route_answer("Route 1", 900, 600, 12345)),
+            );
+        let metrics = Arc::new(Metrics::new());
+        let calculator = WazeRouteCalculator::builder()
+            .set_transport(transport)
+            .set_route_cache(
+                RouteCache::new(16, Duration::from_secs(300))
+                    .with_clock(|| std::time::UNIX_EPOCH + Duration::from_secs(1_800_000_000)),
+            )
+            .set_metrics(metrics.clone())
+            .build();
+
+        assert!(calculator.address_to_coords("Nowhere").is_err());
+
+        let request = RouteRequest::new((32.08, 34.78), (31.768, 35.214));
+        let mut watcher =
+            CommuteWatcher::new(calculator).with_route(WatchedRoute::new("work", request));
+        watcher.check();
+        watcher.check();
+
+        let text = metrics.encode();
+        for line in [
+            r#"waze_requests_total{endpoint="search"} 1"#,
+            r#"waze_requests_total{endpoint="routing"} 1"#,
+            r#"waze_request_duration_seconds_count{endpoint="routing"} 1"#,
+            r#"waze_errors_total{endpoint="search",error="HttpStatus"} 1"#,
+            r#"waze_cache_hits_total{cache="route"} 1"#,
+            r#"waze_cache_misses_total{cache="route"} 1"#,
+            r#"waze_route_duration_seconds{route="work"} 900"#,
+            r#"waze_route_free_flow_duration_seconds{route="work"} 600"#,
+            r#"waze_route_traffic_delay_seconds{route="work"} 300"#,
+            r#"waze_route_distance_meters{route="work"} 12345"#,
+        ] {
+            assert!(text.lines().any(|x| x == line), "{} not in\n{}", line, text);
+        }
+
+        let addr = metrics.serve("127.0.0.1:0").unwrap();
+        let _idle = TcpStream::connect(addr).unwrap();
+        let started = std::time::Instant::now();
+        let response = reqwest::blocking::get(format!("http://{}/metrics", addr)).unwrap();
+        pretty_assertions::assert_eq!(response.status(), 200);
+        assert!(started.elapsed() < Duration::from_secs(4));
+        assert!(response
+            .text()
+            .unwrap()
+            .contains(r#"waze_route_distance_meters{route="work"} 12345"#));
+
+        let response = reqwest::blocking::get(format!("http://{}/", addr)).unwrap();
+        pretty_assertions::assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::helpers::Endpoint;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Struct representing a request budget.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    /// The sustained number of requests per second, finite and positive.
+    pub requests_per_second: f64,
+
+    /// The number of requests that can be sent at once after an idle period.
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a new `RateLimit`.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests_per_second` - The sustained number of requests per second.
+    /// * `burst` - The number of requests that can be sent at once after an idle period.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is zero, negative, infinite or NaN.
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        let limit = RateLimit {
+            requests_per_second,
+            burst,
+        };
+        limit.validate();
+        limit
+    }
+
+    /// Panics on a rate that would never refill the budget, or never empty it.
+    fn validate(&self) {
+        assert!(
+            self.requests_per_second.is_finite() && self.requests_per_second > 0.0,
+            "the rate limit must be a finite and positive number of requests per second, got {}",
+            self.requests_per_second
+        );
+    }
+}
+
+/// A token bucket.
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> Self {
+        limit.validate();
+        TokenBucket {
+            limit,
+            tokens: limit.burst.max(1) as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes a token, returning how long to wait until it is actually available.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let capacity = self.limit.burst.max(1) as f64;
+        let rate = self.limit.requests_per_second;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::try_from_secs_f64(-self.tokens / rate).unwrap_or(Duration::MAX)
+        }
+    }
+}
+
+/// A client-side rate limiter, with separate budgets for the search server and the routing server.
+///
+/// Clones share the same budgets, so one limiter can be handed to every calculator and thread.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    search: Option<Arc<Mutex<TokenBucket>>>,
+    routing: Option<Arc<Mutex<TokenBucket>>>,
+}
+
+impl RateLimiter {
+    /// Creates a new `RateLimiter` giving the same, separate, budget to both servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The budget of each server.
+    ///
+    /// # Returns
+    ///
+    /// A `RateLimiter` instance.
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter::default()
+            .with_search_limit(limit)
+            .with_routing_limit(limit)
+    }
+
+    /// Sets the budget of the search server.
+    pub fn with_search_limit(mut self, limit: RateLimit) -> Self {
+        self.search = Some(Arc::new(Mutex::new(TokenBucket::new(limit))));
+        self
+    }
+
+    /// Sets the budget of the routing server.
+    pub fn with_routing_limit(mut self, limit: RateLimit) -> Self {
+        self.routing = Some(Arc::new(Mutex::new(TokenBucket::new(limit))));
+        self
+    }
+
+    /// Takes a request from the budget of an endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The endpoint the request is sent to.
+    ///
+    /// # Returns
+    ///
+    /// How long to wait before sending the request.
+    pub fn reserve(&self, endpoint: Endpoint) -> Duration {
+        let bucket = match endpoint {
+            Endpoint::Search => &self.search,
+            Endpoint::Routing => &self.routing,
+        };
+
+        let Some(bucket) = bucket else {
+            return Duration::ZERO;
+        };
+
+        let delay = bucket.lock().unwrap().reserve(Instant::now());
+        if !delay.is_zero() {
+            debug!("Rate limited {:?} request, waiting {:?}", endpoint, delay);
+        }
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(RateLimit::new(10.0, 2));
+        let now = bucket.updated;
+
+        pretty_assertions::assert_eq!(bucket.reserve(now), Duration::ZERO);
+        pretty_assertions::assert_eq!(bucket.reserve(now), Duration::ZERO);
+        pretty_assertions::assert_eq!(bucket.reserve(now), Duration::from_millis(100));
+        pretty_assertions::assert_eq!(bucket.reserve(now), Duration::from_millis(200));
+
+        let later = now + Duration::from_secs(10);
+        pretty_assertions::assert_eq!(bucket.reserve(later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_invalid_rate_limit() {
+        for requests_per_second in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let result = std::panic::catch_unwind(|| RateLimit::new(requests_per_second, 1));
+            assert!(result.is_err(), "{} was accepted", requests_per_second);
+
+            let limit = RateLimit {
+                requests_per_second,
+                burst: 1,
+            };
+            let result = std::panic::catch_unwind(|| RateLimiter::new(limit));
+            assert!(result.is_err(), "{} was accepted", requests_per_second);
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter_is_shared() {
+        let limiter = RateLimiter::default().with_routing_limit(RateLimit::new(1.0, 1));
+        let clone = limiter.clone();
+
+        let handle = std::thread::spawn(move || clone.reserve(Endpoint::Routing));
+        let first = handle.join().unwrap();
+        let second = limiter.reserve(Endpoint::Routing);
+
+        pretty_assertions::assert_eq!(first, Duration::ZERO);
+        assert!(second > Duration::from_millis(900));
+        pretty_assertions::assert_eq!(limiter.reserve(Endpoint::Search), Duration::ZERO);
+    }
+}
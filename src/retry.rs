@@ -0,0 +1,173 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// A retry policy for the HTTP calls to the Waze servers, with exponential backoff and jitter.
+///
+/// The default policy makes a single attempt and never retries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry, doubled on every following retry.
+    pub base_delay: Duration,
+
+    /// The maximum delay between two attempts.
+    pub max_delay: Duration,
+
+    /// Whether to randomize each delay between half and all of its value.
+    pub jitter: bool,
+
+    /// Whether to retry when the connection to the server fails.
+    pub retry_on_connect_errors: bool,
+
+    /// Whether to retry when the request times out.
+    pub retry_on_timeouts: bool,
+
+    /// Whether to retry when the server answers with a 5xx status.
+    pub retry_on_server_errors: bool,
+
+    /// Whether to retry when the server answers with a 429 status.
+    pub retry_on_rate_limited: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(1)
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new `RetryPolicy` retrying connect errors, timeouts, 5xx and 429 answers,
+    /// starting with a 200ms delay capped to 5s, with jitter.
+    ///
+    /// A `Retry-After` delay sent by the server replaces the backoff delay, and the request is
+    /// not retried when it is longer than the maximum delay.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - The maximum number of attempts, including the first one.
+    ///
+    /// # Returns
+    ///
+    /// A `RetryPolicy` instance.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retry_on_connect_errors: true,
+            retry_on_timeouts: true,
+            retry_on_server_errors: true,
+            retry_on_rate_limited: true,
+        }
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Sets the maximum delay between two attempts.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets whether to randomize the delays.
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets whether to retry when the connection to the server fails.
+    pub fn with_retry_on_connect_errors(mut self, value: bool) -> Self {
+        self.retry_on_connect_errors = value;
+        self
+    }
+
+    /// Sets whether to retry when the request times out.
+    pub fn with_retry_on_timeouts(mut self, value: bool) -> Self {
+        self.retry_on_timeouts = value;
+        self
+    }
+
+    /// Sets whether to retry when the server answers with a 5xx status.
+    pub fn with_retry_on_server_errors(mut self, value: bool) -> Self {
+        self.retry_on_server_errors = value;
+        self
+    }
+
+    /// Sets whether to retry when the server answers with a 429 status.
+    pub fn with_retry_on_rate_limited(mut self, value: bool) -> Self {
+        self.retry_on_rate_limited = value;
+        self
+    }
+
+    /// Calculates the delay before the next attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `attempt` - The number of the attempt that just failed, starting at 1.
+    ///
+    /// # Returns
+    ///
+    /// The delay to wait before the next attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+
+        if self.jitter {
+            let random = RandomState::new().build_hasher().finish();
+            delay / 2 + delay.mul_f64((random % 1000) as f64 / 2000.0)
+        } else {
+            delay
+        }
+    }
+
+    /// Checks if a failed request should be retried.
+    pub(crate) fn is_retryable_error(&self, error: &reqwest::Error) -> bool {
+        (self.retry_on_connect_errors && error.is_connect())
+            || (self.retry_on_timeouts && error.is_timeout())
+    }
+
+    /// Checks if an answer status should be retried.
+    pub(crate) fn is_retryable_status(&self, status: u16) -> bool {
+        (self.retry_on_server_errors && (500..600).contains(&status))
+            || (self.retry_on_rate_limited && status == 429)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy::new(5)
+            .with_base_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(300))
+            .with_jitter(false);
+
+        pretty_assertions::assert_eq!(policy.delay(1), Duration::from_millis(100));
+        pretty_assertions::assert_eq!(policy.delay(2), Duration::from_millis(200));
+        pretty_assertions::assert_eq!(policy.delay(3), Duration::from_millis(300));
+        pretty_assertions::assert_eq!(policy.delay(40), Duration::from_millis(300));
+
+        let policy = policy.with_jitter(true);
+        for attempt in 1..5 {
+            let delay = policy.delay(attempt);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(300));
+        }
+
+        assert!(policy.is_retryable_status(502));
+        assert!(policy.is_retryable_status(429));
+        assert!(!policy.is_retryable_status(404));
+        assert!(!policy
+            .with_retry_on_rate_limited(false)
+            .is_retryable_status(429));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::waze_structs::{Coordinates, WazeRoute};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+/// The key of a cached route, built by `WazeRouteCalculator::route_cache_key`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct RouteCacheKey {
+    pub(crate) base_url: String,
+    pub(crate) region: usize,
+    pub(crate) from: (i64, i64),
+    pub(crate) to: (i64, i64),
+    pub(crate) options: String,
+    pub(crate) vehicle_type: String,
+    pub(crate) avoid_subscription_roads: bool,
+    pub(crate) at: i64,
+    pub(crate) n_paths: usize,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    routes: Vec<WazeRoute>,
+    bucket: u64,
+    inserted: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<RouteCacheKey, CacheEntry>,
+    clock: u64,
+}
+
+/// A routing answer cache, so repeated requests for the same route don't call the routing server.
+///
+/// Routes are keyed by the server, the rounded start and end coordinates, the route options, the
+/// vehicle type, the subscription flag and the departure offset. A cached route is served as long as the current
+/// time falls in the same time bucket it was stored in.
+#[derive(Debug)]
+pub struct RouteCache {
+    state: Mutex<CacheState>,
+    capacity: usize,
+    time_bucket: Duration,
+    precision: i32,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    now: fn() -> SystemTime,
+}
+
+impl RouteCache {
+    /// Creates a new `RouteCache`, rounding coordinates to 4 decimals (about 11 meters).
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of routes to keep, the oldest ones are evicted first.
+    /// * `time_bucket` - The length of the time buckets, e.g. 5 minutes.
+    ///
+    /// # Returns
+    ///
+    /// A `RouteCache` instance.
+    pub fn new(capacity: usize, time_bucket: Duration) -> Self {
+        RouteCache {
+            state: Mutex::new(CacheState::default()),
+            capacity,
+            time_bucket,
+            precision: 4,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            now: SystemTime::now,
+        }
+    }
+
+    /// Replaces the clock picking the time bucket, for tests.
+    #[cfg(test)]
+    pub(crate) fn with_clock(mut self, now: fn() -> SystemTime) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Sets the number of decimals the coordinates are rounded to in the cache key.
+    ///
+    /// # Arguments
+    ///
+    /// * `precision` - The number of decimals to keep.
+    ///
+    /// # Returns
+    ///
+    /// The updated `RouteCache` instance.
+    pub fn with_precision(mut self, precision: i32) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Rounds coordinates to the cache precision.
+    pub(crate) fn round(&self, coords: &Coordinates) -> (i64, i64) {
+        let scale = 10f64.powi(self.precision);
+        (
+            (coords.latitude * scale).round() as i64,
+            (coords.longitude * scale).round() as i64,
+        )
+    }
+
+    /// Returns the time bucket `time` falls in.
+    fn bucket(&self, time: SystemTime) -> u64 {
+        let elapsed = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        elapsed / self.time_bucket.as_secs().max(1)
+    }
+
+    /// Looks up a route, counting a hit or a miss.
+    pub(crate) fn get(&self, key: &RouteCacheKey) -> Option<Vec<WazeRoute>> {
+        let bucket = self.bucket((self.now)());
+        let mut state = self.state.lock().unwrap();
+
+        match state.entries.get(key) {
+            Some(entry) if entry.bucket == bucket => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                debug!("Route cache hit: {:?}", key);
+                Some(entry.routes.clone())
+            }
+            Some(_) => {
+                state.entries.remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Stores a route in the current time bucket.
+    pub(crate) fn insert(&self, key: RouteCacheKey, routes: Vec<WazeRoute>) {
+        let bucket = self.bucket((self.now)());
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+
+        let entry = CacheEntry {
+            routes,
+            bucket,
+            inserted: state.clock,
+        };
+        state.entries.insert(key, entry);
+        state.entries.retain(|_, entry| entry.bucket == bucket);
+
+        while state.entries.len() > self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted)
+                .map(|(key, _)| key.clone())
+                .unwrap();
+            state.entries.remove(&oldest);
+        }
+    }
+
+    /// Returns the number of cache hits.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of cache misses.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Removes every cached route.
+    pub fn invalidate(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    /// Removes the cached routes between two coordinates, for every option and departure time.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The starting coordinates.
+    /// * `to` - The ending coordinates.
+    pub fn invalidate_route(&self, from: &Coordinates, to: &Coordinates) {
+        let (from, to) = (self.round(from), self.round(to));
+
+        self.state
+            .lock()
+            .unwrap()
+            .entries
+            .retain(|key, _| key.from != from || key.to != to);
+    }
+
+    /// Returns the number of cached routes.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Checks if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static NOW: AtomicU64 = AtomicU64::new(1_800_000_000);
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(NOW.load(Ordering::Relaxed))
+    }
+
+    fn create_key(from: (i64, i64)) -> RouteCacheKey {
+        RouteCacheKey {
+            base_url: String::new(),
+            region: 0,
+            from,
+            to: (0, 0),
+            options: String::new(),
+            vehicle_type: String::new(),
+            avoid_subscription_roads: false,
+            at: 0,
+            n_paths: 1,
+        }
+    }
+
+    #[test]
+    fn test_route_cache() {
+        let cache = RouteCache::new(2, Duration::from_secs(3600)).with_clock(now);
+
+        pretty_assertions::assert_eq!(cache.get(&create_key((1, 1))), None);
+        cache.insert(create_key((1, 1)), vec![WazeRoute::default()]);
+        cache.insert(create_key((2, 2)), vec![]);
+        cache.insert(create_key((3, 3)), vec![]);
+
+        pretty_assertions::assert_eq!(cache.len(), 2);
+        pretty_assertions::assert_eq!(cache.get(&create_key((1, 1))), None);
+        pretty_assertions::assert_eq!(cache.get(&create_key((3, 3))), Some(vec![]));
+        pretty_assertions::assert_eq!(cache.hits(), 1);
+        pretty_assertions::assert_eq!(cache.misses(), 2);
+
+        let rounded = cache.round(&Coordinates::new(0.00021, 0.00029));
+        pretty_assertions::assert_eq!(rounded, (2, 3));
+
+        cache.invalidate_route(&Coordinates::new(0.0003, 0.0003), &Coordinates::default());
+        pretty_assertions::assert_eq!(cache.len(), 1);
+        cache.invalidate();
+        pretty_assertions::assert_eq!(cache.is_empty(), true);
+
+        let mut other_server = create_key((1, 1));
+        other_server.base_url = "http://localhost/".to_string();
+        cache.insert(create_key((1, 1)), vec![]);
+        pretty_assertions::assert_eq!(cache.get(&other_server), None);
+
+        NOW.fetch_add(3600, Ordering::Relaxed);
+        pretty_assertions::assert_eq!(cache.get(&create_key((1, 1))), None);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::waze_structs::RouteSummary;
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+/// Escapes the characters that are not allowed in XML text and attribute values.
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl RouteSummary {
+    /// Converts the route geometry to a GeoJSON `LineString`.
+    ///
+    /// # Returns
+    ///
+    /// A GeoJSON geometry object with `[longitude, latitude]` positions.
+    pub fn to_geojson(&self) -> Value {
+        json!({
+            "type": "LineString",
+            "coordinates": self
+                .geometry
+                .iter()
+                .map(|point| [point.x, point.y])
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// Converts the route to a GeoJSON `Feature` with the route geometry and its summary as properties.
+    ///
+    /// # Returns
+    ///
+    /// A GeoJSON feature object.
+    pub fn to_geojson_feature(&self) -> Value {
+        json!({
+            "type": "Feature",
+            "geometry": self.to_geojson(),
+            "properties": {
+                "routeName": self.route_name,
+                "distance": self.distance,
+                "duration": self.duration.as_secs(),
+                "freeFlowDuration": self.free_flow_duration.as_secs(),
+                "trafficDelay": self.traffic_delay.as_secs(),
+            },
+        })
+    }
+
+    /// Converts the route geometry to a GPX 1.1 document with a single track.
+    ///
+    /// # Returns
+    ///
+    /// The GPX document as a string.
+    pub fn to_gpx(&self) -> String {
+        let mut gpx = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gpx version=\"1.1\" creator=\"waze-rs\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+        );
+        gpx.push_str("  <trk>\n");
+        let _ = writeln!(gpx, "    <name>{}</name>", xml_escape(&self.route_name));
+        gpx.push_str("    <trkseg>\n");
+        for point in &self.geometry {
+            let _ = writeln!(
+                gpx,
+                "      <trkpt lat=\"{}\" lon=\"{}\"/>",
+                point.y, point.x
+            );
+        }
+        gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+        gpx
+    }
+
+    /// Converts the route geometry to a KML document with a single `LineString` placemark.
+    ///
+    /// # Returns
+    ///
+    /// The KML document as a string.
+    pub fn to_kml(&self) -> String {
+        let coordinates = self
+            .geometry
+            .iter()
+            .map(|point| format!("{},{}", point.x, point.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut kml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n",
+        );
+        kml.push_str("  <Document>\n    <Placemark>\n");
+        let _ = writeln!(kml, "      <name>{}</name>", xml_escape(&self.route_name));
+        kml.push_str("      <LineString>\n");
+        let _ = writeln!(kml, "        <coordinates>{}</coordinates>", coordinates);
+        kml.push_str("      </LineString>\n    </Placemark>\n  </Document>\n</kml>\n");
+        kml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::waze_structs::{RouteSummary, WazeCoord};
+    use std::time::{Duration, SystemTime};
+
+    fn create_mock_route_summary() -> RouteSummary {
+        RouteSummary {
+            route_name: "Route 1 & Ayalon".to_string(),
+            duration: Duration::from_secs(600),
+            free_flow_duration: Duration::from_secs(480),
+            traffic_delay: Duration::from_secs(120),
+            distance: 5000,
+            departure_time: SystemTime::UNIX_EPOCH,
+            arrival_time: SystemTime::UNIX_EPOCH + Duration::from_secs(600),
+            segments: vec![],
+            geometry: vec![
+                WazeCoord { x: 34.78, y: 32.08 },
+                WazeCoord { x: 34.8, y: 32.1 },
+            ],
+            maneuvers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_to_geojson_feature() {
+        let feature = create_mock_route_summary().to_geojson_feature();
+
+        pretty_assertions::assert_eq!(feature["geometry"]["type"], "LineString");
+        pretty_assertions::assert_eq!(
+            feature["geometry"]["coordinates"],
+            serde_json::json!([[34.78, 32.08], [34.8, 32.1]])
+        );
+        pretty_assertions::assert_eq!(feature["properties"]["trafficDelay"], 120);
+    }
+
+    #[test]
+    fn test_to_gpx_and_kml() {
+        let summary = create_mock_route_summary();
+
+        let gpx = summary.to_gpx();
+        assert!(gpx.contains("<name>Route 1 &amp; Ayalon</name>"));
+        assert!(gpx.contains("<trkpt lat=\"32.08\" lon=\"34.78\"/>"));
+
+        let kml = summary.to_kml();
+        assert!(kml.contains("<coordinates>34.78,32.08 34.8,32.1</coordinates>"));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::waze_structs::{RouteSummary, WazeRoute};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// Enum representing the kind of a maneuver.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ManeuverKind {
+    /// Start of the route
+    Depart,
+
+    /// Continue straight
+    Continue,
+
+    /// Turn left
+    TurnLeft,
+
+    /// Turn right
+    TurnRight,
+
+    /// Keep left at a fork
+    KeepLeft,
+
+    /// Keep right at a fork
+    KeepRight,
+
+    /// Take the exit on the left
+    ExitLeft,
+
+    /// Take the exit on the right
+    ExitRight,
+
+    /// Make a U-turn
+    UTurn,
+
+    /// Enter a roundabout
+    RoundaboutEnter,
+
+    /// Exit a roundabout
+    RoundaboutExit,
+
+    /// Turn left at a roundabout
+    RoundaboutLeft,
+
+    /// Turn right at a roundabout
+    RoundaboutRight,
+
+    /// Go straight at a roundabout
+    RoundaboutStraight,
+
+    /// Make a U-turn at a roundabout
+    RoundaboutUTurn,
+
+    /// Arrive at the destination
+    Arrive,
+
+    /// An opcode this crate does not know about
+    Other(String),
+}
+
+impl ManeuverKind {
+    /// Converts a Waze instruction opcode to a `ManeuverKind`.
+    ///
+    /// # Arguments
+    /// * `opcode` - The Waze instruction opcode.
+    ///
+    /// # Returns
+    /// * The matching `ManeuverKind`, or `ManeuverKind::Other` for unknown opcodes.
+    pub fn from_opcode(opcode: &str) -> Self {
+        match opcode {
+            "CONTINUE" => ManeuverKind::Continue,
+            "TURN_LEFT" => ManeuverKind::TurnLeft,
+            "TURN_RIGHT" => ManeuverKind::TurnRight,
+            "KEEP_LEFT" => ManeuverKind::KeepLeft,
+            "KEEP_RIGHT" => ManeuverKind::KeepRight,
+            "EXIT_LEFT" => ManeuverKind::ExitLeft,
+            "EXIT_RIGHT" => ManeuverKind::ExitRight,
+            "UTURN" => ManeuverKind::UTurn,
+            "ROUNDABOUT_ENTER" => ManeuverKind::RoundaboutEnter,
+            "ROUNDABOUT_EXIT" => ManeuverKind::RoundaboutExit,
+            "ROUNDABOUT_LEFT" | "ROUNDABOUT_EXIT_LEFT" => ManeuverKind::RoundaboutLeft,
+            "ROUNDABOUT_RIGHT" | "ROUNDABOUT_EXIT_RIGHT" => ManeuverKind::RoundaboutRight,
+            "ROUNDABOUT_STRAIGHT" => ManeuverKind::RoundaboutStraight,
+            "ROUNDABOUT_U" => ManeuverKind::RoundaboutUTurn,
+            "APPROACHING_DESTINATION" => ManeuverKind::Arrive,
+            other => ManeuverKind::Other(other.to_string()),
+        }
+    }
+
+    /// Checks if the maneuver happens at a roundabout.
+    pub fn is_roundabout(&self) -> bool {
+        matches!(
+            self,
+            ManeuverKind::RoundaboutEnter
+                | ManeuverKind::RoundaboutExit
+                | ManeuverKind::RoundaboutLeft
+                | ManeuverKind::RoundaboutRight
+                | ManeuverKind::RoundaboutStraight
+                | ManeuverKind::RoundaboutUTurn
+        )
+    }
+}
+
+/// Struct representing a single turn-by-turn maneuver.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Maneuver {
+    /// The kind of the maneuver.
+    pub kind: ManeuverKind,
+
+    /// The street the maneuver leads onto.
+    pub street_name: Option<String>,
+
+    /// The distance in meters from this maneuver to the next one.
+    pub distance: u64,
+
+    /// The exit number for roundabouts.
+    pub exit_number: Option<u32>,
+}
+
+/// Formats a distance in meters for the rendered instructions.
+fn format_distance(distance: u64) -> String {
+    if distance < 1000 {
+        format!("{} m", distance)
+    } else {
+        format!("{:.1} km", distance as f64 / 1000.0)
+    }
+}
+
+impl fmt::Display for Maneuver {
+    /// Renders the maneuver as a readable instruction, e.g. "Turn left onto Ayalon, then continue for 1.2 km".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match &self.kind {
+            ManeuverKind::Depart => "Head out".to_string(),
+            ManeuverKind::Continue => "Continue".to_string(),
+            ManeuverKind::TurnLeft => "Turn left".to_string(),
+            ManeuverKind::TurnRight => "Turn right".to_string(),
+            ManeuverKind::KeepLeft => "Keep left".to_string(),
+            ManeuverKind::KeepRight => "Keep right".to_string(),
+            ManeuverKind::ExitLeft => "Take the exit on the left".to_string(),
+            ManeuverKind::ExitRight => "Take the exit on the right".to_string(),
+            ManeuverKind::UTurn => "Make a U-turn".to_string(),
+            ManeuverKind::RoundaboutExit => "Exit the roundabout".to_string(),
+            ManeuverKind::Arrive => return write!(f, "Arrive at your destination"),
+            ManeuverKind::Other(opcode) => opcode.to_lowercase().replace('_', " "),
+            _ => match self.exit_number {
+                Some(exit) => format!("At the roundabout, take exit {}", exit),
+                None => "Enter the roundabout".to_string(),
+            },
+        };
+
+        write!(f, "{}", action)?;
+
+        if let Some(street_name) = &self.street_name {
+            let preposition = match self.kind {
+                ManeuverKind::Depart | ManeuverKind::Continue => "on",
+                _ => "onto",
+            };
+            write!(f, " {} {}", preposition, street_name)?;
+        }
+
+        if self.distance > 0 {
+            write!(f, ", then continue for {}", format_distance(self.distance))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the maneuvers of a route from the instructions attached to its segments.
+///
+/// Waze attaches the instruction to the segment that ends at the maneuver, so the street of
+/// the following segment is the one the maneuver leads onto.
+///
+/// # Arguments
+///
+/// * `route` - The route returned by the routing server.
+///
+/// # Returns
+///
+/// The maneuvers of the route, starting with a `ManeuverKind::Depart` one.
+pub(crate) fn parse_maneuvers(route: &WazeRoute) -> Vec<Maneuver> {
+    let street_name = |index: usize| {
+        route
+            .results
+            .get(index)
+            .and_then(|segment| segment.street)
+            .and_then(|street| route.street_names.get(street).cloned().flatten())
+            .filter(|name| !name.is_empty())
+    };
+
+    if route.results.iter().all(|x| x.instruction.is_none()) {
+        return vec![];
+    }
+
+    let mut maneuvers = vec![Maneuver {
+        kind: ManeuverKind::Depart,
+        street_name: street_name(0),
+        distance: 0,
+        exit_number: None,
+    }];
+
+    for (index, segment) in route.results.iter().enumerate() {
+        if let Some(maneuver) = maneuvers.last_mut() {
+            maneuver.distance = maneuver
+                .distance
+                .saturating_add(segment.length.max(0) as u64);
+        }
+
+        let Some(instruction) = &segment.instruction else {
+            continue;
+        };
+
+        if instruction.opcode == "NONE" || instruction.opcode.is_empty() {
+            continue;
+        }
+
+        let kind = ManeuverKind::from_opcode(&instruction.opcode);
+        let exit_number = instruction
+            .arg
+            .filter(|arg| kind.is_roundabout() && *arg > 0)
+            .map(|arg| arg as u32);
+
+        maneuvers.push(Maneuver {
+            street_name: street_name(index + 1).or_else(|| instruction.name.clone()),
+            kind,
+            distance: 0,
+            exit_number,
+        });
+    }
+
+    maneuvers
+}
+
+impl RouteSummary {
+    /// Renders the maneuvers of the route as readable directions.
+    ///
+    /// # Returns
+    ///
+    /// A vector with one line of text per maneuver.
+    pub fn directions(&self) -> Vec<String> {
+        self.maneuvers.iter().map(|x| x.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_maneuvers() {
+        let route: WazeRoute = serde_json::from_str(r#"{"streetNames":["Herzl",null,"Ayalon"],"results":[{"path":null,"street":0,"length":300,"crossTime":30,"crossTimeWithoutRealTime":30,"instruction":{"opcode":"NONE","arg":0}},{"path":null,"street":0,"length":200,"crossTime":20,"crossTimeWithoutRealTime":20,"instruction":{"opcode":"ROUNDABOUT_RIGHT","arg":2}},{"path":null,"street":1,"length":100,"crossTime":10,"crossTimeWithoutRealTime":10,"instruction":{"opcode":"TURN_LEFT","arg":0}},{"path":null,"street":2,"length":1500,"crossTime":60,"crossTimeWithoutRealTime":60,"instruction":{"opcode":"APPROACHING_DESTINATION","arg":0}}]}"#).unwrap();
+
+        let maneuvers = parse_maneuvers(&route);
+
+        pretty_assertions::assert_eq!(maneuvers.len(), 4);
+        pretty_assertions::assert_eq!(maneuvers[0].kind, ManeuverKind::Depart);
+        pretty_assertions::assert_eq!(maneuvers[0].distance, 500);
+        pretty_assertions::assert_eq!(maneuvers[1].kind, ManeuverKind::RoundaboutRight);
+        pretty_assertions::assert_eq!(maneuvers[1].exit_number, Some(2));
+        pretty_assertions::assert_eq!(maneuvers[1].street_name, None);
+        pretty_assertions::assert_eq!(maneuvers[2].street_name.as_deref(), Some("Ayalon"));
+        pretty_assertions::assert_eq!(maneuvers[2].distance, 1500);
+
+        let directions: Vec<String> = maneuvers.iter().map(|x| x.to_string()).collect();
+        pretty_assertions::assert_eq!(
+            directions,
+            vec![
+                "Head out on Herzl, then continue for 500 m",
+                "At the roundabout, take exit 2, then continue for 100 m",
+                "Turn left onto Ayalon, then continue for 1.5 km",
+                "Arrive at your destination",
+            ]
+        );
+    }
+}
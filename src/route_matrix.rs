@@ -0,0 +1,464 @@
+use crate::waze_route_calculator::{
+    Coordinates, RouteRequest, RouteSummary, WazeRouteCalculatorError,
+};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The route between one origin and one destination of a `RouteMatrix`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatrixCell {
+    /// The name of the route.
+    pub route_name: String,
+
+    /// The route duration with realtime traffic.
+    pub duration: Duration,
+
+    /// The route duration without realtime traffic.
+    pub free_flow_duration: Duration,
+
+    /// The extra time caused by traffic.
+    pub traffic_delay: Duration,
+
+    /// The route distance in meters.
+    pub distance: u64,
+}
+
+impl From<&RouteSummary> for MatrixCell {
+    fn from(summary: &RouteSummary) -> Self {
+        MatrixCell {
+            route_name: summary.route_name.clone(),
+            duration: summary.duration,
+            free_flow_duration: summary.free_flow_duration,
+            traffic_delay: summary.traffic_delay,
+            distance: summary.distance,
+        }
+    }
+}
+
+/// The result of a matrix cell. Errors are shared, as a failed geocoding fails a whole row or column.
+pub type MatrixResult = Result<MatrixCell, Arc<WazeRouteCalculatorError>>;
+
+/// Travel times and distances between every origin and every destination.
+#[derive(Clone, Debug)]
+pub struct RouteMatrix {
+    /// The origin addresses, one row each.
+    pub origins: Vec<String>,
+
+    /// The destination addresses, one column each.
+    pub destinations: Vec<String>,
+
+    /// The cells, `cells[origin][destination]`.
+    pub cells: Vec<Vec<MatrixResult>>,
+}
+
+impl RouteMatrix {
+    /// Arranges the cells, listed row after row, into a matrix.
+    fn new(origins: &[&str], destinations: &[&str], cells: Vec<MatrixResult>) -> Self {
+        let mut cells = cells.into_iter();
+
+        RouteMatrix {
+            origins: origins.iter().map(|x| x.to_string()).collect(),
+            destinations: destinations.iter().map(|x| x.to_string()).collect(),
+            cells: origins
+                .iter()
+                .map(|_| cells.by_ref().take(destinations.len()).collect())
+                .collect(),
+        }
+    }
+
+    /// Returns the cell of an origin and a destination.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The origin index.
+    /// * `destination` - The destination index.
+    ///
+    /// # Returns
+    ///
+    /// The cell result, or `None` if an index is out of bounds.
+    pub fn get(&self, origin: usize, destination: usize) -> Option<&MatrixResult> {
+        self.cells.get(origin)?.get(destination)
+    }
+
+    /// Converts the matrix to JSON, with the `MatrixCell` field names, durations in seconds and
+    /// distances in meters.
+    ///
+    /// # Returns
+    ///
+    /// A JSON object with the `origins`, the `destinations` and the `cells` rows, failed cells
+    /// being `{"error": "..."}` objects.
+    pub fn to_json(&self) -> Value {
+        let cells = self
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell {
+                        Ok(cell) => json!({
+                            "route_name": cell.route_name,
+                            "distance": cell.distance,
+                            "duration": cell.duration.as_secs(),
+                            "free_flow_duration": cell.free_flow_duration.as_secs(),
+                            "traffic_delay": cell.traffic_delay.as_secs(),
+                        }),
+                        Err(e) => json!({ "error": e.to_string() }),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "origins": self.origins,
+            "destinations": self.destinations,
+            "cells": cells,
+        })
+    }
+
+    /// Converts the matrix to CSV, one line per origin and destination pair, with durations in
+    /// seconds and distances in meters.
+    ///
+    /// # Returns
+    ///
+    /// The CSV document, with a header line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "origin,destination,route_name,duration,free_flow_duration,traffic_delay,distance,error\n",
+        );
+
+        for (origin, row) in self.origins.iter().zip(&self.cells) {
+            for (destination, cell) in self.destinations.iter().zip(row) {
+                let fields = match cell {
+                    Ok(cell) => [
+                        cell.route_name.clone(),
+                        cell.duration.as_secs().to_string(),
+                        cell.free_flow_duration.as_secs().to_string(),
+                        cell.traffic_delay.as_secs().to_string(),
+                        cell.distance.to_string(),
+                        String::new(),
+                    ],
+                    Err(e) => [
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                        String::new(),
+                        e.to_string(),
+                    ],
+                };
+
+                let line = [origin.as_str(), destination.as_str()]
+                    .into_iter()
+                    .chain(fields.iter().map(String::as_str))
+                    .map(csv_escape)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                csv.push_str(&line);
+                csv.push('\n');
+            }
+        }
+
+        csv
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Returns the addresses of a matrix without duplicates, in order of appearance.
+fn unique_addresses<'a>(origins: &[&'a str], destinations: &[&'a str]) -> Vec<&'a str> {
+    let mut addresses = vec![];
+    for address in origins.iter().chain(destinations) {
+        if !addresses.contains(address) {
+            addresses.push(*address);
+        }
+    }
+    addresses
+}
+
+/// Builds the route request of each cell, row after row, or the geocoding error failing it.
+fn cell_requests(
+    origins: &[&str],
+    destinations: &[&str],
+    coords: &HashMap<&str, Result<Coordinates, Arc<WazeRouteCalculatorError>>>,
+) -> Vec<Result<RouteRequest, Arc<WazeRouteCalculatorError>>> {
+    origins
+        .iter()
+        .flat_map(|origin| {
+            destinations
+                .iter()
+                .map(move |destination| (origin, destination))
+        })
+        .map(|(origin, destination)| {
+            Ok(RouteRequest::new(
+                coords[origin].clone()?,
+                coords[destination].clone()?,
+            ))
+        })
+        .collect()
+}
+
+/// Applies a function to every item, on up to `concurrency` threads, keeping the items order.
+#[cfg(feature = "blocking")]
+fn map_concurrently<T: Sync, R: Send>(
+    items: &[T],
+    concurrency: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(items.iter().map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is mapped"))
+        .collect()
+}
+
+#[cfg(feature = "blocking")]
+impl crate::waze_route_calculator::WazeRouteCalculator {
+    /// Calculates the routes between every origin and every destination.
+    ///
+    /// Each unique address is geocoded once, then the routes are requested on up to
+    /// `concurrency` threads. A failed geocoding or route only fails its own cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `origins` - The origin addresses or coordinates.
+    /// * `destinations` - The destination addresses or coordinates.
+    /// * `concurrency` - The maximum number of requests in flight.
+    ///
+    /// # Returns
+    ///
+    /// The `RouteMatrix` of the origins and destinations.
+    pub fn calculate_matrix(
+        &self,
+        origins: &[&str],
+        destinations: &[&str],
+        concurrency: usize,
+    ) -> RouteMatrix {
+        let addresses = unique_addresses(origins, destinations);
+        let coords = map_concurrently(&addresses, concurrency, |address| {
+            self.address_to_coords(address).map_err(Arc::new)
+        });
+        let coords = addresses.into_iter().zip(coords).collect::<HashMap<_, _>>();
+
+        let requests = cell_requests(origins, destinations, &coords);
+        let cells = map_concurrently(&requests, concurrency, |request| {
+            let request = request.as_ref().map_err(Arc::clone)?;
+            self.route(request)
+                .map(|summary| MatrixCell::from(&summary))
+                .map_err(Arc::new)
+        });
+
+        RouteMatrix::new(origins, destinations, cells)
+    }
+}
+
+#[cfg(feature = "async")]
+impl crate::async_waze_route_calculator::AsyncWazeRouteCalculator {
+    /// Calculates the routes between every origin and every destination.
+    ///
+    /// Each unique address is geocoded once, then the routes are requested with up to
+    /// `concurrency` requests in flight. A failed geocoding or route only fails its own cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `origins` - The origin addresses or coordinates.
+    /// * `destinations` - The destination addresses or coordinates.
+    /// * `concurrency` - The maximum number of requests in flight.
+    ///
+    /// # Returns
+    ///
+    /// The `RouteMatrix` of the origins and destinations.
+    pub async fn calculate_matrix(
+        &self,
+        origins: &[&str],
+        destinations: &[&str],
+        concurrency: usize,
+    ) -> RouteMatrix {
+        use futures_util::stream::{self, StreamExt};
+
+        let addresses = unique_addresses(origins, destinations);
+        let coords = stream::iter(&addresses)
+            .map(|address| async move { self.address_to_coords(address).await.map_err(Arc::new) })
+            .buffered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        let coords = addresses.into_iter().zip(coords).collect::<HashMap<_, _>>();
+
+        let requests = cell_requests(origins, destinations, &coords);
+        let cells = stream::iter(&requests)
+            .map(|request| async move {
+                let request = request.as_ref().map_err(Arc::clone)?;
+                self.route(request)
+                    .await
+                    .map(|summary| MatrixCell::from(&summary))
+                    .map_err(Arc::new)
+            })
+            .buffered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        RouteMatrix::new(origins, destinations, cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::route_answer;
+    use crate::transport::{FakeTransport, TransportResponse};
+    use crate::waze_route_calculator::WazeRouteCalculator;
+
+    const ORIGINS: [&str; 2] = ["32.08,34.78", "Nowhere"];
+    const DESTINATIONS: [&str; 3] = ["31.768,35.214", "Nowhere", "32.79,34.99"];
+
+    fn create_fake_transport() -> Arc<FakeTransport> {
+        Arc::new(
+            FakeTransport::new()
+                .with_response("SearchServer", TransportResponse::new(200, "[]"))
+                .with_response(
+                    "routingRequest",
+                    TransportResponse::new(200, route_answer("Route 1, Ayalon", 600, 300, 1000)),
+                ),
+        )
+    }
+
+    fn assert_matrix(matrix: &RouteMatrix, transport: &FakeTransport) {
+        let errors = matrix
+            .cells
+            .iter()
+            .map(|row| row.iter().map(Result::is_err).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(
+            errors,
+            vec![vec![false, true, false], vec![true, true, true]]
+        );
+        pretty_assertions::assert_eq!(
+            matrix.get(0, 2).unwrap().as_ref().unwrap().duration,
+            Duration::from_secs(600)
+        );
+        assert!(matches!(
+            matrix.get(1, 0).unwrap().as_ref().unwrap_err().as_ref(),
+            WazeRouteCalculatorError::NoResults(_)
+        ));
+        assert!(matrix.get(2, 0).is_none());
+
+        let requests = transport.requests();
+        pretty_assertions::assert_eq!(
+            requests
+                .iter()
+                .filter(|x| x.url.contains("SearchServer"))
+                .count(),
+            1
+        );
+        pretty_assertions::assert_eq!(
+            requests
+                .iter()
+                .filter(|x| x.url.contains("routingRequest"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_calculate_matrix() {
+        let transport = create_fake_transport();
+        let calculator = WazeRouteCalculator::builder()
+            .set_transport(transport.clone())
+            .build();
+
+        let matrix = calculator.calculate_matrix(&ORIGINS, &DESTINATIONS, 4);
+        assert_matrix(&matrix, &transport);
+
+        let empty = calculator.calculate_matrix(&ORIGINS, &[], 4);
+        assert!(empty.cells.len() == 2 && empty.cells.iter().all(Vec::is_empty));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "async")]
+    async fn test_async_calculate_matrix() {
+        let transport = create_fake_transport();
+        let calculator = WazeRouteCalculator::builder()
+            .set_async_transport(transport.clone())
+            .build_async();
+
+        let matrix = calculator
+            .calculate_matrix(&ORIGINS, &DESTINATIONS, 4)
+            .await;
+        assert_matrix(&matrix, &transport);
+    }
+
+    #[test]
+    fn test_matrix_export() {
+        let cell = MatrixCell {
+            route_name: "Route 1, Ayalon".to_string(),
+            duration: Duration::from_secs(600),
+            free_flow_duration: Duration::from_secs(300),
+            traffic_delay: Duration::from_secs(300),
+            distance: 1000,
+        };
+        let matrix = RouteMatrix::new(
+            &["A"],
+            &["B", "C"],
+            vec![
+                Ok(cell),
+                Err(Arc::new(WazeRouteCalculatorError::NoResults(
+                    "C".to_string(),
+                ))),
+            ],
+        );
+
+        pretty_assertions::assert_eq!(
+            matrix.to_csv(),
+            "origin,destination,route_name,duration,free_flow_duration,traffic_delay,distance,error\n\
+             A,B,\"Route 1, Ayalon\",600,300,300,1000,\n\
+             A,C,,,,,,No results for C\n"
+        );
+        pretty_assertions::assert_eq!(
+            matrix.to_json(),
+            json!({
+                "origins": ["A"],
+                "destinations": ["B", "C"],
+                "cells": [[
+                    {
+                        "route_name": "Route 1, Ayalon",
+                        "distance": 1000,
+                        "duration": 600,
+                        "free_flow_duration": 300,
+                        "traffic_delay": 300,
+                    },
+                    { "error": "No results for C" },
+                ]],
+            })
+        );
+    }
+}
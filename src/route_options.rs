@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The routing options sent to the Waze routing server in the `options` query parameter.
+///
+/// Options left to `None` are not sent, so the server default applies. The default options avoid
+/// trails and allow toll roads and ferries.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RouteOptions {
+    /// Whether to avoid trails and dirt roads.
+    pub avoid_trails: Option<bool>,
+
+    /// Whether to avoid toll roads.
+    pub avoid_toll_roads: Option<bool>,
+
+    /// Whether to avoid ferries.
+    pub avoid_ferries: Option<bool>,
+
+    /// Whether to avoid highways.
+    pub avoid_highways: Option<bool>,
+
+    /// Whether HOV/carpool lanes can be used.
+    pub allow_hov_lanes: Option<bool>,
+
+    /// Whether U-turns are allowed.
+    pub allow_u_turns: Option<bool>,
+
+    /// Whether to avoid difficult intersections.
+    pub avoid_difficult_intersections: Option<bool>,
+
+    /// Raw options, sent as is and taking precedence over the typed ones.
+    pub raw: BTreeMap<String, String>,
+}
+
+impl RouteOptions {
+    pub const AVOID_TRAILS: &'static str = "AVOID_TRAILS";
+    pub const AVOID_TOLL_ROADS: &'static str = "AVOID_TOLL_ROADS";
+    pub const AVOID_FERRIES: &'static str = "AVOID_FERRIES";
+    pub const AVOID_HIGHWAYS: &'static str = "AVOID_PRIMARIES";
+    pub const ALLOW_HOV_LANES: &'static str = "ALLOW_HOV";
+    pub const ALLOW_U_TURNS: &'static str = "ALLOW_UTURNS";
+    pub const AVOID_DIFFICULT_INTERSECTIONS: &'static str = "AVOID_DANGEROUS_TURNS";
+
+    /// Creates a new `RouteOptions` without any option, leaving every choice to the server.
+    pub fn empty() -> Self {
+        RouteOptions {
+            avoid_trails: None,
+            avoid_toll_roads: None,
+            avoid_ferries: None,
+            avoid_highways: None,
+            allow_hov_lanes: None,
+            allow_u_turns: None,
+            avoid_difficult_intersections: None,
+            raw: BTreeMap::new(),
+        }
+    }
+
+    /// Sets whether to avoid trails and dirt roads.
+    pub fn with_avoid_trails(mut self, value: bool) -> Self {
+        self.avoid_trails = Some(value);
+        self
+    }
+
+    /// Sets whether to avoid toll roads.
+    pub fn with_avoid_toll_roads(mut self, value: bool) -> Self {
+        self.avoid_toll_roads = Some(value);
+        self
+    }
+
+    /// Sets whether to avoid ferries.
+    pub fn with_avoid_ferries(mut self, value: bool) -> Self {
+        self.avoid_ferries = Some(value);
+        self
+    }
+
+    /// Sets whether to avoid highways.
+    pub fn with_avoid_highways(mut self, value: bool) -> Self {
+        self.avoid_highways = Some(value);
+        self
+    }
+
+    /// Sets whether HOV/carpool lanes can be used.
+    pub fn with_allow_hov_lanes(mut self, value: bool) -> Self {
+        self.allow_hov_lanes = Some(value);
+        self
+    }
+
+    /// Sets whether U-turns are allowed.
+    pub fn with_allow_u_turns(mut self, value: bool) -> Self {
+        self.allow_u_turns = Some(value);
+        self
+    }
+
+    /// Sets whether to avoid difficult intersections.
+    pub fn with_avoid_difficult_intersections(mut self, value: bool) -> Self {
+        self.avoid_difficult_intersections = Some(value);
+        self
+    }
+
+    /// Sets a raw option, for flags without a typed field.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The option name, e.g. `"AVOID_LONG_TRAILS"`.
+    /// * `value` - The option value, usually `"t"` or `"f"`.
+    ///
+    /// # Returns
+    ///
+    /// The updated `RouteOptions` instance.
+    pub fn with_raw(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.raw.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns the options to send, sorted by name.
+    ///
+    /// # Returns
+    ///
+    /// A map of option names to values.
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        let flags = [
+            (RouteOptions::AVOID_TRAILS, self.avoid_trails),
+            (RouteOptions::AVOID_TOLL_ROADS, self.avoid_toll_roads),
+            (RouteOptions::AVOID_FERRIES, self.avoid_ferries),
+            (RouteOptions::AVOID_HIGHWAYS, self.avoid_highways),
+            (RouteOptions::ALLOW_HOV_LANES, self.allow_hov_lanes),
+            (RouteOptions::ALLOW_U_TURNS, self.allow_u_turns),
+            (
+                RouteOptions::AVOID_DIFFICULT_INTERSECTIONS,
+                self.avoid_difficult_intersections,
+            ),
+        ];
+
+        let mut options = flags
+            .iter()
+            .filter_map(|(name, value)| {
+                value.map(|value| (name.to_string(), if value { "t" } else { "f" }.to_string()))
+            })
+            .collect::<BTreeMap<_, _>>();
+        options.extend(self.raw.clone());
+        options
+    }
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        RouteOptions::empty()
+            .with_avoid_trails(true)
+            .with_avoid_toll_roads(false)
+            .with_avoid_ferries(false)
+    }
+}
+
+/// Formats the options as the `options` query parameter, e.g. `AVOID_FERRIES:f,AVOID_TRAILS:t`.
+impl fmt::Display for RouteOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = self
+            .to_map()
+            .iter()
+            .map(|(name, value)| format!("{}:{}", name, value))
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(f, "{}", options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_options() {
+        pretty_assertions::assert_eq!(
+            RouteOptions::default().to_string(),
+            "AVOID_FERRIES:f,AVOID_TOLL_ROADS:f,AVOID_TRAILS:t"
+        );
+        pretty_assertions::assert_eq!(RouteOptions::empty().to_string(), "");
+
+        let options = RouteOptions::default()
+            .with_avoid_highways(true)
+            .with_allow_hov_lanes(true)
+            .with_allow_u_turns(false)
+            .with_avoid_difficult_intersections(true)
+            .with_raw("AVOID_LONG_TRAILS", "t")
+            .with_raw("AVOID_TRAILS", "f");
+        pretty_assertions::assert_eq!(
+            options.to_string(),
+            "ALLOW_HOV:t,ALLOW_UTURNS:f,AVOID_DANGEROUS_TURNS:t,AVOID_FERRIES:f,\
+             AVOID_LONG_TRAILS:t,AVOID_PRIMARIES:t,AVOID_TOLL_ROADS:f,AVOID_TRAILS:f"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::helpers::{DepartureTime, VehicleType};
+use crate::route_options::RouteOptions;
+use crate::waze_structs::Coordinates;
+
+/// An immutable route query, with per-request overrides of the calculator options.
+///
+/// Unlike `set_address` and `set_coords`, routing a `RouteRequest` with `route()` doesn't touch
+/// the calculator, so a single `Arc<WazeRouteCalculator>` can serve concurrent requests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteRequest {
+    /// The starting coordinates.
+    pub origin: Coordinates,
+
+    /// The ending coordinates.
+    pub destination: Coordinates,
+
+    /// The departure time, the calculator one when `None`.
+    pub departure_time: Option<DepartureTime>,
+
+    /// The vehicle type, the calculator one when `None`.
+    pub vehicle_type: Option<VehicleType>,
+
+    /// The routing options, the calculator ones when `None`.
+    pub options: Option<RouteOptions>,
+
+    /// Whether to avoid toll roads, on top of the routing options.
+    pub avoid_toll_roads: Option<bool>,
+
+    /// Whether to avoid ferries, on top of the routing options.
+    pub avoid_ferries: Option<bool>,
+
+    /// Whether to avoid subscription roads, the calculator setting when `None`.
+    pub avoid_subscription_roads: Option<bool>,
+}
+
+impl RouteRequest {
+    /// Creates a new `RouteRequest` using the calculator options.
+    ///
+    /// # Arguments
+    ///
+    /// * `origin` - The starting coordinates.
+    /// * `destination` - The ending coordinates.
+    ///
+    /// # Returns
+    ///
+    /// A `RouteRequest` instance.
+    pub fn new(origin: impl Into<Coordinates>, destination: impl Into<Coordinates>) -> Self {
+        RouteRequest {
+            origin: origin.into(),
+            destination: destination.into(),
+            departure_time: None,
+            vehicle_type: None,
+            options: None,
+            avoid_toll_roads: None,
+            avoid_ferries: None,
+            avoid_subscription_roads: None,
+        }
+    }
+
+    /// Sets the departure time.
+    pub fn with_departure_time(mut self, departure_time: impl Into<DepartureTime>) -> Self {
+        self.departure_time = Some(departure_time.into());
+        self
+    }
+
+    /// Sets the vehicle type.
+    pub fn with_vehicle_type(mut self, vehicle_type: VehicleType) -> Self {
+        self.vehicle_type = Some(vehicle_type);
+        self
+    }
+
+    /// Sets the routing options, replacing the calculator ones.
+    pub fn with_options(mut self, options: RouteOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Sets whether to avoid toll roads.
+    pub fn with_avoid_toll_roads(mut self, value: bool) -> Self {
+        self.avoid_toll_roads = Some(value);
+        self
+    }
+
+    /// Sets whether to avoid ferries.
+    pub fn with_avoid_ferries(mut self, value: bool) -> Self {
+        self.avoid_ferries = Some(value);
+        self
+    }
+
+    /// Sets whether to avoid subscription roads.
+    pub fn with_avoid_subscription_roads(mut self, value: bool) -> Self {
+        self.avoid_subscription_roads = Some(value);
+        self
+    }
+}
@@ -0,0 +1,54 @@
+//! Waze answers shared by the tests, in the shape of the real ones.
+
+use serde_json::json;
+
+/// Returns a routing server answer with a route of two segments adding up to the given totals.
+///
+/// Like the real answers, the route geometry is the `coords` array next to the `response`.
+///
+/// # Arguments
+///
+/// * `route_name` - The name of the route.
+/// * `cross_time` - The realtime duration of the route, in seconds.
+/// * `free_flow_time` - The duration of the route without traffic, in seconds.
+/// * `length` - The distance of the route, in meters.
+pub(crate) fn route_answer(
+    route_name: &str,
+    cross_time: u64,
+    free_flow_time: u64,
+    length: u64,
+) -> String {
+    let segment = |id: u64, x: f64, y: f64, (cross_time, free_flow_time, length)| {
+        json!({
+            "path": {"segmentId": id, "nodeId": id + 100, "x": x, "y": y, "direction": true},
+            "street": 0,
+            "length": length,
+            "crossTime": cross_time,
+            "crossTimeWithoutRealTime": free_flow_time,
+        })
+    };
+    let first = (cross_time / 2, free_flow_time / 2, length / 2);
+    let second = (
+        cross_time - first.0,
+        free_flow_time - first.1,
+        length - first.2,
+    );
+
+    json!({
+        "response": {
+            "routeName": route_name,
+            "routeType": ["FASTEST"],
+            "results": [
+                segment(1, 34.78, 32.08, first),
+                segment(2, 34.79, 32.09, second),
+            ],
+            "streetNames": [route_name],
+        },
+        "coords": [
+            {"x": 34.78, "y": 32.08, "z": "NaN"},
+            {"x": 34.785, "y": 32.085, "z": "NaN"},
+            {"x": 34.79, "y": 32.09, "z": "NaN"},
+        ],
+    })
+    .to_string()
+}
@@ -0,0 +1,609 @@
+use crate::helpers::{write_file_atomically, Endpoint};
+use crate::http_client::{HttpClientConfig, HttpClients};
+use crate::waze_route_calculator::WazeRouteCalculatorError;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// A GET request to one of the Waze servers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransportRequest {
+    /// The server the request is sent to.
+    pub endpoint: Endpoint,
+
+    /// The URL, without the query string.
+    pub url: String,
+
+    /// The query parameters, in order.
+    pub query: Vec<(String, String)>,
+
+    /// The request headers.
+    pub headers: Vec<(String, String)>,
+}
+
+impl TransportRequest {
+    /// Returns the query parameters as a `name=value&...` string, for logs and errors.
+    pub fn query_string(&self) -> String {
+        self.query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+/// The answer of a Waze server.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransportResponse {
+    /// The HTTP status code.
+    pub status: u16,
+
+    /// The answer body.
+    pub body: String,
+
+    /// The delay the server asks to wait before retrying, from its `Retry-After` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<Duration>,
+}
+
+impl TransportResponse {
+    /// Creates a new `TransportResponse`.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The HTTP status code.
+    /// * `body` - The answer body.
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        TransportResponse {
+            status,
+            body: body.into(),
+            retry_after: None,
+        }
+    }
+
+    /// Sets the delay the server asks to wait before retrying.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Checks if the status is 2xx.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Parses the body as JSON.
+    pub fn json(&self) -> Result<Value, WazeRouteCalculatorError> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+}
+
+/// Sends the requests of `WazeRouteCalculator`.
+///
+/// Implement it to route the calls through another HTTP stack, or use `FakeTransport` and
+/// `ReplayTransport` to test code using the calculator without a network.
+pub trait Transport: Debug + Send + Sync {
+    /// Sends a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to send.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the answer, whatever its status, or an error if no answer was received.
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError>;
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        (**self).send(request)
+    }
+}
+
+/// The future returned by `AsyncTransport::send`.
+#[cfg(feature = "async")]
+pub type TransportFuture<'a> = std::pin::Pin<
+    Box<
+        dyn std::future::Future<Output = Result<TransportResponse, WazeRouteCalculatorError>>
+            + Send
+            + 'a,
+    >,
+>;
+
+/// Sends the requests of `AsyncWazeRouteCalculator`.
+#[cfg(feature = "async")]
+pub trait AsyncTransport: Debug + Send + Sync {
+    /// Sends a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to send.
+    ///
+    /// # Returns
+    ///
+    /// A future resolving to the answer, whatever its status, or an error if no answer was received.
+    fn send<'a>(&'a self, request: &'a TransportRequest) -> TransportFuture<'a>;
+}
+
+#[cfg(feature = "async")]
+impl<T: AsyncTransport + ?Sized> AsyncTransport for Arc<T> {
+    fn send<'a>(&'a self, request: &'a TransportRequest) -> TransportFuture<'a> {
+        (**self).send(request)
+    }
+}
+
+/// The default transport, sending the requests with reqwest.
+#[derive(Debug, Default)]
+pub struct ReqwestTransport {
+    clients: HttpClients,
+}
+
+impl ReqwestTransport {
+    /// Creates a new `ReqwestTransport`, building its clients from the settings on first use.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The HTTP client settings.
+    ///
+    /// # Returns
+    ///
+    /// A `ReqwestTransport` instance.
+    pub fn new(config: HttpClientConfig) -> Self {
+        ReqwestTransport {
+            clients: HttpClients::new(
+                config,
+                #[cfg(feature = "blocking")]
+                None,
+                #[cfg(feature = "async")]
+                None,
+            ),
+        }
+    }
+
+    /// Creates a `ReqwestTransport` from already built clients.
+    pub(crate) fn from_clients(clients: HttpClients) -> Self {
+        ReqwestTransport { clients }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl Transport for ReqwestTransport {
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        let mut builder = self
+            .clients
+            .blocking()?
+            .get(&request.url)
+            .query(&request.query);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+
+        let response = builder.send()?;
+        debug!("Response: {:?}", response);
+
+        Ok(TransportResponse {
+            status: response.status().as_u16(),
+            retry_after: retry_after(response.headers()),
+            body: response.text()?,
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTransport for ReqwestTransport {
+    fn send<'a>(&'a self, request: &'a TransportRequest) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let mut builder = self
+                .clients
+                .non_blocking()?
+                .get(&request.url)
+                .query(&request.query);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+
+            let response = builder.send().await?;
+            debug!("Response: {:?}", response);
+
+            Ok(TransportResponse {
+                status: response.status().as_u16(),
+                retry_after: retry_after(response.headers()),
+                body: response.text().await?,
+            })
+        })
+    }
+}
+
+/// Parses the `Retry-After` header of an answer, when it is a number of seconds.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// An in-memory transport answering with canned responses, for tests.
+///
+/// Responses are matched by a part of the request URL, e.g. `"SearchServer"` or `"routingRequest"`.
+/// Several responses for the same part are served in order, the last one being repeated.
+/// Share it through an `Arc` to look at the received requests after handing it to a calculator.
+#[derive(Debug, Default)]
+pub struct FakeTransport {
+    responses: Mutex<Vec<(String, VecDeque<TransportResponse>)>>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl FakeTransport {
+    /// Creates a new `FakeTransport` without responses.
+    pub fn new() -> Self {
+        FakeTransport::default()
+    }
+
+    /// Adds a response to the requests whose URL contains `url_part`.
+    ///
+    /// # Arguments
+    ///
+    /// * `url_part` - A part of the request URL.
+    /// * `response` - The response to answer with.
+    ///
+    /// # Returns
+    ///
+    /// The updated `FakeTransport` instance.
+    pub fn with_response(self, url_part: &str, response: TransportResponse) -> Self {
+        {
+            let mut responses = self.responses.lock().unwrap();
+            match responses.iter_mut().find(|(part, _)| part == url_part) {
+                Some((_, queue)) => queue.push_back(response),
+                None => responses.push((url_part.to_string(), VecDeque::from([response]))),
+            }
+        }
+        self
+    }
+
+    /// Returns the requests received so far.
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn answer(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        self.requests.lock().unwrap().push(request.clone());
+
+        let mut responses = self.responses.lock().unwrap();
+        let (_, queue) = responses
+            .iter_mut()
+            .find(|(part, _)| request.url.contains(part.as_str()))
+            .ok_or_else(|| {
+                WazeRouteCalculatorError::TransportError(format!(
+                    "No fake answer for {}",
+                    request.url
+                ))
+            })?;
+
+        if queue.len() > 1 {
+            Ok(queue.pop_front().unwrap())
+        } else {
+            Ok(queue[0].clone())
+        }
+    }
+}
+
+impl Transport for FakeTransport {
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        self.answer(request)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTransport for FakeTransport {
+    fn send<'a>(&'a self, request: &'a TransportRequest) -> TransportFuture<'a> {
+        Box::pin(std::future::ready(self.answer(request)))
+    }
+}
+
+/// A recorded request and its answer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The request sent.
+    pub request: TransportRequest,
+
+    /// The answer received.
+    pub response: TransportResponse,
+}
+
+/// A transport recording every answer of another transport to a JSON cassette file,
+/// to be replayed later with `ReplayTransport`.
+///
+/// The answers are kept in memory and written by `save()`, or when the transport is dropped.
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    path: PathBuf,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl<T> RecordingTransport<T> {
+    /// Creates a new `RecordingTransport`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The transport sending the requests, usually a `ReqwestTransport`.
+    /// * `path` - The cassette file, overwritten on every save.
+    ///
+    /// # Returns
+    ///
+    /// A `RecordingTransport` instance.
+    pub fn new(inner: T, path: impl AsRef<Path>) -> Self {
+        RecordingTransport {
+            inner,
+            path: path.as_ref().to_path_buf(),
+            interactions: Mutex::new(vec![]),
+        }
+    }
+
+    /// Writes the answers recorded so far to the cassette file.
+    ///
+    /// # Returns
+    ///
+    /// A result indicating success or an error if the file can't be written.
+    pub fn save(&self) -> Result<(), WazeRouteCalculatorError> {
+        let interactions = self
+            .interactions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let json = serde_json::to_string_pretty(&*interactions)?;
+        write_file_atomically(&self.path, &json)?;
+        Ok(())
+    }
+
+    fn record(&self, request: &TransportRequest, response: &TransportResponse) {
+        self.interactions.lock().unwrap().push(Interaction {
+            request: request.clone(),
+            response: response.clone(),
+        });
+    }
+}
+
+impl<T> Drop for RecordingTransport<T> {
+    fn drop(&mut self) {
+        let interactions = self
+            .interactions
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if interactions.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.save() {
+            error!("Failed to save the cassette: {}", e);
+        }
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        let response = self.inner.send(request)?;
+        self.record(request, &response);
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: AsyncTransport> AsyncTransport for RecordingTransport<T> {
+    fn send<'a>(&'a self, request: &'a TransportRequest) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let response = self.inner.send(request).await?;
+            self.record(request, &response);
+            Ok(response)
+        })
+    }
+}
+
+/// A transport replaying the answers of a cassette recorded by `RecordingTransport`.
+///
+/// Requests are matched by URL and query parameters. Recorded answers to the same request are
+/// replayed in order, the last one being repeated.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    interactions: Vec<Interaction>,
+    replayed: Mutex<HashMap<usize, usize>>,
+}
+
+impl ReplayTransport {
+    /// Creates a new `ReplayTransport` from recorded interactions.
+    pub fn new(interactions: Vec<Interaction>) -> Self {
+        ReplayTransport {
+            interactions,
+            replayed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Loads a cassette file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The cassette file written by `RecordingTransport`.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `ReplayTransport` instance or an error if the file can't be read.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, WazeRouteCalculatorError> {
+        let interactions = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(ReplayTransport::new(interactions))
+    }
+
+    fn answer(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        let matches = self
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| {
+                interaction.request.url == request.url && interaction.request.query == request.query
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let Some(first) = matches.first() else {
+            return Err(WazeRouteCalculatorError::TransportError(format!(
+                "No recorded answer for {} {:?}",
+                request.url, request.query
+            )));
+        };
+
+        let mut replayed = self.replayed.lock().unwrap();
+        let count = replayed.entry(*first).or_default();
+        let index = matches[(*count).min(matches.len() - 1)];
+        *count += 1;
+
+        Ok(self.interactions[index].response.clone())
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        self.answer(request)
+    }
+}
+
+#[cfg(feature = "async")]
+impl AsyncTransport for ReplayTransport {
+    fn send<'a>(&'a self, request: &'a TransportRequest) -> TransportFuture<'a> {
+        Box::pin(std::future::ready(self.answer(request)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_request(query: &str) -> TransportRequest {
+        TransportRequest {
+            endpoint: Endpoint::Search,
+            url: "https://www.waze.com/il-SearchServer/mozi".to_string(),
+            query: vec![("q".to_string(), query.to_string())],
+            headers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_fake_transport() {
+        let transport = FakeTransport::new()
+            .with_response("SearchServer", TransportResponse::new(503, ""))
+            .with_response("SearchServer", TransportResponse::new(200, "[]"));
+
+        pretty_assertions::assert_eq!(
+            Transport::send(&transport, &create_request("a"))
+                .unwrap()
+                .status,
+            503
+        );
+        pretty_assertions::assert_eq!(
+            Transport::send(&transport, &create_request("a"))
+                .unwrap()
+                .status,
+            200
+        );
+        pretty_assertions::assert_eq!(
+            Transport::send(&transport, &create_request("a"))
+                .unwrap()
+                .status,
+            200
+        );
+        pretty_assertions::assert_eq!(transport.requests().len(), 3);
+
+        let mut request = create_request("a");
+        request.url = "https://www.waze.com/RoutingManager/routingRequest".to_string();
+        assert!(matches!(
+            Transport::send(&transport, &request),
+            Err(WazeRouteCalculatorError::TransportError(_))
+        ));
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("waze-rs-cassette-{}.json", std::process::id()));
+        let fake = FakeTransport::new()
+            .with_response("SearchServer", TransportResponse::new(500, "error"))
+            .with_response("SearchServer", TransportResponse::new(200, "[]"));
+
+        let recorder = RecordingTransport::new(fake, &path);
+        Transport::send(&recorder, &create_request("a")).unwrap();
+        Transport::send(&recorder, &create_request("a")).unwrap();
+        Transport::send(&recorder, &create_request("b")).unwrap();
+        assert!(!path.exists());
+        recorder.save().unwrap();
+
+        let replay = ReplayTransport::from_file(&path).unwrap();
+        pretty_assertions::assert_eq!(
+            Transport::send(&replay, &create_request("b"))
+                .unwrap()
+                .status,
+            200
+        );
+        pretty_assertions::assert_eq!(
+            Transport::send(&replay, &create_request("a"))
+                .unwrap()
+                .status,
+            500
+        );
+        pretty_assertions::assert_eq!(
+            Transport::send(&replay, &create_request("a"))
+                .unwrap()
+                .status,
+            200
+        );
+        pretty_assertions::assert_eq!(
+            Transport::send(&replay, &create_request("a"))
+                .unwrap()
+                .status,
+            200
+        );
+        assert!(Transport::send(&replay, &create_request("c")).is_err());
+
+        Transport::send(&recorder, &create_request("c")).unwrap();
+        drop(recorder);
+        let replay = ReplayTransport::from_file(&path).unwrap();
+        pretty_assertions::assert_eq!(
+            Transport::send(&replay, &create_request("c"))
+                .unwrap()
+                .status,
+            200
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
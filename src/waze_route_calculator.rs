@@ -1,42 +1,157 @@
-use crate::helpers::{Region, VehicleType};
-pub use crate::waze_structs::{Bound, Coordinates, WazeAddressAnswer, WazeResult};
-use reqwest::header::{HeaderMap, HeaderValue, REFERER, USER_AGENT};
+use crate::geocode_cache::GeocodeCache;
+use crate::helpers::{haversine_distance, DepartureTime, Endpoint, Region, VehicleType};
+use crate::http_client::{HttpClientConfig, HttpClients};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::route_cache::{RouteCache, RouteCacheKey};
+use crate::route_instructions::parse_maneuvers;
+pub use crate::route_options::RouteOptions;
+pub use crate::route_request::RouteRequest;
+#[cfg(feature = "async")]
+use crate::transport::AsyncTransport;
+#[cfg(feature = "blocking")]
+use crate::transport::Transport;
+use crate::transport::{ReqwestTransport, TransportRequest, TransportResponse};
+pub use crate::waze_structs::{
+    Bound, Coordinates, MultiLegRoute, RouteSummary, WazeAddress, WazeAddressAnswer,
+    WazeAddressCoordinates, WazeCoord, WazeResult, WazeRoute,
+};
+use serde::Deserialize;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 /// Waze route calculator error types.
 #[derive(Error, Debug)]
 pub enum WazeRouteCalculatorError {
-    #[error("Failed to get coordinates")]
-    FailedToGetCoordinates,
-
     #[error("Failed to get route")]
     FailedToGetRoute,
 
     #[error("Waze API error: {0}")]
     WazeApiError(String),
 
-    #[error("Networking error")]
+    #[error("No results for {0}")]
+    NoResults(String),
+
+    #[error("HTTP status {status} from {url}?{query}: {body}")]
+    HttpStatus {
+        status: u16,
+        url: String,
+        query: String,
+        body: String,
+    },
+
+    #[error("HTML answer (captcha or block page) with status {status} from {url}?{query}: {body}")]
+    HtmlResponse {
+        status: u16,
+        url: String,
+        query: String,
+        body: String,
+    },
+
+    #[error("Invalid Waze response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Invalid departure time: {0}")]
+    InvalidDepartureTime(String),
+
+    #[error("The {0} coordinates are not set")]
+    MissingCoordinates(&'static str),
+
+    #[error("Networking error: {0}")]
     NetworkError(#[from] reqwest::Error),
 
-    #[error("Serde error")]
+    #[error("Serde error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
+    #[error("Notification error: {0}")]
+    NotificationError(String),
+
     #[error("Unknown error")]
     UnknownError,
 }
 
+impl WazeRouteCalculatorError {
+    /// The maximum number of characters of a response body kept in an error.
+    pub const MAX_BODY_LENGTH: usize = 512;
+
+    /// Checks if the request may succeed when sent again later: connect errors, timeouts,
+    /// 5xx answers and 429 answers.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WazeRouteCalculatorError::NetworkError(e) => e.is_connect() || e.is_timeout(),
+            WazeRouteCalculatorError::HttpStatus { status, .. } => {
+                *status == 429 || (500..600).contains(status)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if Waze is throttling the client: a 429 answer, or an HTML captcha or block page.
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(
+            self,
+            WazeRouteCalculatorError::HttpStatus { status: 429, .. }
+                | WazeRouteCalculatorError::HtmlResponse { .. }
+        )
+    }
+
+    /// Returns the HTTP status of the answer that caused the error, if there was one.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            WazeRouteCalculatorError::HttpStatus { status, .. }
+            | WazeRouteCalculatorError::HtmlResponse { status, .. } => Some(*status),
+            WazeRouteCalculatorError::NetworkError(e) => e.status().map(|x| x.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+/// Truncates a response body to `WazeRouteCalculatorError::MAX_BODY_LENGTH` characters.
+fn truncate(body: &str) -> String {
+    match body
+        .char_indices()
+        .nth(WazeRouteCalculatorError::MAX_BODY_LENGTH)
+    {
+        Some((index, _)) => format!("{}...", &body[..index]),
+        None => body.to_string(),
+    }
+}
+
 /// A builder for the `WazeRouteCalculator` struct.
 #[derive(Debug)]
 pub struct WazeRouteCalculatorBuilder {
     pub region: Region,
     pub vehicle_type: VehicleType,
-    pub avoid_toll_roads: bool,
+    pub route_options: RouteOptions,
     pub avoid_subscription_roads: bool,
-    pub avoid_ferries: bool,
+    pub departure_time: DepartureTime,
     pub base_url: String,
+    pub geocode_cache: Option<Arc<GeocodeCache>>,
+    pub route_cache: Option<Arc<RouteCache>>,
+    pub retry_policy: RetryPolicy,
+    pub rate_limiter: Option<RateLimiter>,
+    pub http_config: HttpClientConfig,
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<Arc<Metrics>>,
+    #[cfg(feature = "blocking")]
+    pub blocking_client: Option<reqwest::blocking::Client>,
+    #[cfg(feature = "async")]
+    pub client: Option<reqwest::Client>,
+    #[cfg(feature = "blocking")]
+    pub transport: Option<Arc<dyn Transport>>,
+    #[cfg(feature = "async")]
+    pub async_transport: Option<Arc<dyn AsyncTransport>>,
 }
 
 impl WazeRouteCalculatorBuilder {
@@ -94,7 +209,7 @@ impl WazeRouteCalculatorBuilder {
     ///
     /// The updated `WazeRouteCalculatorBuilder` instance.
     pub fn set_avoid_toll_roads(mut self, value: bool) -> Self {
-        self.avoid_toll_roads = value;
+        self.route_options.avoid_toll_roads = Some(value);
         self
     }
 
@@ -108,7 +223,37 @@ impl WazeRouteCalculatorBuilder {
     ///
     /// The updated `WazeRouteCalculatorBuilder` instance.
     pub fn set_avoid_ferries(mut self, value: bool) -> Self {
-        self.avoid_ferries = value;
+        self.route_options.avoid_ferries = Some(value);
+        self
+    }
+
+    /// Sets the routing options, replacing the toll roads and ferries settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `route_options` - The routing options to set.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_route_options(mut self, route_options: RouteOptions) -> Self {
+        debug!("route_options: {}", route_options);
+        self.route_options = route_options;
+        self
+    }
+
+    /// Sets the departure time for the route calculator.
+    ///
+    /// # Arguments
+    ///
+    /// * `departure_time` - The departure time to set.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_departure_time(mut self, departure_time: impl Into<DepartureTime>) -> Self {
+        self.departure_time = departure_time.into();
+        debug!("departure_time: {:?}", self.departure_time);
         self
     }
 
@@ -127,41 +272,232 @@ impl WazeRouteCalculatorBuilder {
         self
     }
 
+    /// Sets the geocoding cache used by `address_to_coords`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache to use, it can be shared between calculators.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_geocode_cache(mut self, cache: impl Into<Arc<GeocodeCache>>) -> Self {
+        self.geocode_cache = Some(cache.into());
+        self
+    }
+
+    /// Sets the routing answer cache used by `calculate_route` and friends.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache to use, it can be shared between calculators.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_route_cache(mut self, cache: impl Into<Arc<RouteCache>>) -> Self {
+        self.route_cache = Some(cache.into());
+        self
+    }
+
+    /// Sets the Prometheus metrics counting the requests, errors and cache hits.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - The metrics to update, they can be shared between calculators.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    #[cfg(feature = "metrics")]
+    pub fn set_metrics(mut self, metrics: impl Into<Arc<Metrics>>) -> Self {
+        self.metrics = Some(metrics.into());
+        self
+    }
+
+    /// Sets the retry policy of the HTTP calls to the Waze servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_policy` - The retry policy to set.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        debug!("retry_policy: {:?}", retry_policy);
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the client-side rate limiter of the HTTP calls to the Waze servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `rate_limiter` - The rate limiter to use, its clones share the same budgets.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Sets the maximum time to establish a connection to the Waze servers.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The connect timeout.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http_config.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time to wait for data from the Waze servers.
+    ///
+    /// The blocking client has no read timeout, so it bounds the whole request there.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The read timeout.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_read_timeout(mut self, timeout: Duration) -> Self {
+        self.http_config.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the HTTP, HTTPS or SOCKS proxy to send the requests through.
+    ///
+    /// # Arguments
+    ///
+    /// * `proxy` - The proxy, SOCKS proxies need the `socks` feature.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn set_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.http_config.proxy = Some(proxy);
+        self
+    }
+
+    /// Adds a root certificate to trust, e.g. the CA of a corporate proxy.
+    ///
+    /// # Arguments
+    ///
+    /// * `certificate` - The certificate to add.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    pub fn add_root_certificate(mut self, certificate: reqwest::Certificate) -> Self {
+        self.http_config.root_certificates.push(certificate);
+        self
+    }
+
+    /// Sets the blocking client to use, instead of building one from the HTTP settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client to use.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    #[cfg(feature = "blocking")]
+    pub fn set_blocking_client(mut self, client: reqwest::blocking::Client) -> Self {
+        self.blocking_client = Some(client);
+        self
+    }
+
+    /// Sets the async client to use, instead of building one from the HTTP settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client to use.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    #[cfg(feature = "async")]
+    pub fn set_client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets the transport sending the requests of the blocking calculator, instead of reqwest.
+    ///
+    /// # Arguments
+    ///
+    /// * `transport` - The transport to use, e.g. a `FakeTransport` or a `ReplayTransport` in tests.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    #[cfg(feature = "blocking")]
+    pub fn set_transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sets the transport sending the requests of the async calculator, instead of reqwest.
+    ///
+    /// # Arguments
+    ///
+    /// * `transport` - The transport to use, e.g. a `FakeTransport` or a `ReplayTransport` in tests.
+    ///
+    /// # Returns
+    ///
+    /// The updated `WazeRouteCalculatorBuilder` instance.
+    #[cfg(feature = "async")]
+    pub fn set_async_transport(mut self, transport: impl AsyncTransport + 'static) -> Self {
+        self.async_transport = Some(Arc::new(transport));
+        self
+    }
+
     /// Builds the `WazeRouteCalculator` instance.
     ///
     /// # Returns
     ///
     /// A `WazeRouteCalculator` instance with the configured options.
     pub fn build(self) -> WazeRouteCalculator {
-        let mut route_options = HashMap::new();
-        route_options.insert("AVOID_TRAILS".to_string(), "t".to_string());
-        route_options.insert(
-            "AVOID_TOLL_ROADS".to_string(),
-            if self.avoid_toll_roads {
-                "t".to_string()
-            } else {
-                "f".to_string()
-            },
-        );
-        route_options.insert(
-            "AVOID_FERRIES".to_string(),
-            if self.avoid_ferries {
-                "t".to_string()
-            } else {
-                "f".to_string()
-            },
-        );
+        debug!("Route options: {}", self.route_options);
 
-        debug!("Route options: {:?}", route_options);
+        let reqwest_transport = Arc::new(ReqwestTransport::from_clients(HttpClients::new(
+            self.http_config,
+            #[cfg(feature = "blocking")]
+            self.blocking_client,
+            #[cfg(feature = "async")]
+            self.client,
+        )));
 
         WazeRouteCalculator {
             region: self.region,
             vehicle_type: self.vehicle_type,
             start_coords: None,
             end_coords: None,
+            via_coords: vec![],
+            departure_time: self.departure_time,
             avoid_subscription_roads: self.avoid_subscription_roads,
-            route_options,
+            route_options: self.route_options,
             base_url: self.base_url,
+            geocode_cache: self.geocode_cache,
+            route_cache: self.route_cache,
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limiter,
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics,
+            #[cfg(feature = "blocking")]
+            transport: self.transport.unwrap_or_else(|| reqwest_transport.clone()),
+            #[cfg(feature = "async")]
+            async_transport: self.async_transport.unwrap_or(reqwest_transport),
         }
     }
 }
@@ -173,9 +509,21 @@ pub struct WazeRouteCalculator {
     pub vehicle_type: VehicleType,
     pub start_coords: Option<Coordinates>,
     pub end_coords: Option<Coordinates>,
-    route_options: HashMap<String, String>,
+    pub via_coords: Vec<Coordinates>,
+    pub departure_time: DepartureTime,
+    route_options: RouteOptions,
     avoid_subscription_roads: bool,
     base_url: String,
+    geocode_cache: Option<Arc<GeocodeCache>>,
+    route_cache: Option<Arc<RouteCache>>,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+    #[cfg(feature = "blocking")]
+    transport: Arc<dyn Transport>,
+    #[cfg(feature = "async")]
+    pub(crate) async_transport: Arc<dyn AsyncTransport>,
 }
 
 impl WazeRouteCalculator {
@@ -189,185 +537,519 @@ impl WazeRouteCalculator {
             region: Region::EU,
             vehicle_type: VehicleType::CAR,
             avoid_subscription_roads: false,
-            avoid_toll_roads: false,
-            avoid_ferries: false,
+            route_options: RouteOptions::default(),
+            departure_time: DepartureTime::Now,
             base_url: WazeRouteCalculator::WAZE_URL.to_string(),
+            geocode_cache: None,
+            route_cache: None,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            http_config: HttpClientConfig::default(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "blocking")]
+            blocking_client: None,
+            #[cfg(feature = "async")]
+            client: None,
+            #[cfg(feature = "blocking")]
+            transport: None,
+            #[cfg(feature = "async")]
+            async_transport: None,
         }
     }
 
-    /// Sets the start and end coordinates based on the provided addresses.
+    /// Sets the start and end coordinates directly, without geocoding.
     ///
     /// # Arguments
     ///
-    /// * `start_address` - The starting address.
-    /// * `end_address` - The ending address.
+    /// * `start_coords` - The starting coordinates, or a `(latitude, longitude)` pair.
+    /// * `end_coords` - The ending coordinates, or a `(latitude, longitude)` pair.
     ///
     /// # Returns
     ///
-    /// A result containing a mutable reference to the `WazeRouteCalculator` instance or an error.
-    pub fn set_address(
+    /// A mutable reference to the `WazeRouteCalculator` instance.
+    pub fn set_coords(
         &mut self,
-        start_address: &str,
-        end_address: &str,
-    ) -> Result<&mut Self, WazeRouteCalculatorError> {
-        self.start_coords = Some(self.address_to_coords(start_address)?);
-        self.end_coords = Some(self.address_to_coords(end_address)?);
+        start_coords: impl Into<Coordinates>,
+        end_coords: impl Into<Coordinates>,
+    ) -> &mut Self {
+        self.set_start_coords(start_coords)
+            .set_end_coords(end_coords)
+    }
 
-        debug!(
-            "Start coordinates: {}, {}",
-            self.start_coords.unwrap().latitude,
-            self.start_coords.unwrap().longitude
-        );
+    /// Sets the via points, the ordered intermediate stops between the start and the end.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The via points coordinates, or `(latitude, longitude)` pairs.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `WazeRouteCalculator` instance.
+    pub fn set_via_coords<T: Into<Coordinates>>(
+        &mut self,
+        coords: impl IntoIterator<Item = T>,
+    ) -> &mut Self {
+        self.via_coords = coords.into_iter().map(Into::into).collect();
+        debug!("Via coordinates: {:?}", self.via_coords);
+        self
+    }
 
+    /// Sets the start coordinates directly, without geocoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The starting coordinates, or a `(latitude, longitude)` pair.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `WazeRouteCalculator` instance.
+    pub fn set_start_coords(&mut self, coords: impl Into<Coordinates>) -> &mut Self {
+        let coords = coords.into();
         debug!(
-            "End coordinates: {}, {}",
-            self.end_coords.unwrap().latitude,
-            self.end_coords.unwrap().longitude
+            "Start coordinates: {}, {}",
+            coords.latitude, coords.longitude
         );
+        self.start_coords = Some(coords);
+        self
+    }
 
-        Ok(self)
+    /// Sets the end coordinates directly, without geocoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The ending coordinates, or a `(latitude, longitude)` pair.
+    ///
+    /// # Returns
+    ///
+    /// A mutable reference to the `WazeRouteCalculator` instance.
+    pub fn set_end_coords(&mut self, coords: impl Into<Coordinates>) -> &mut Self {
+        let coords = coords.into();
+        debug!("End coordinates: {}, {}", coords.latitude, coords.longitude);
+        self.end_coords = Some(coords);
+        self
     }
 
     /// Constructs the headers required for the HTTP request.
     ///
     /// # Returns
     ///
-    /// A `HeaderMap` containing the necessary headers.
-    fn construct_headers(&self) -> HeaderMap {
-        let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0"));
-        headers.insert(
-            REFERER,
-            HeaderValue::from_str(self.base_url.as_str()).unwrap(),
-        );
-        headers
+    /// The names and values of the necessary headers.
+    pub(crate) fn construct_headers(&self) -> Vec<(String, String)> {
+        vec![
+            ("User-Agent".to_string(), "Mozilla/5.0".to_string()),
+            ("Referer".to_string(), self.base_url.clone()),
+        ]
     }
 
-    /// Converts an address to coordinates.
+    /// Builds a transport request with the necessary headers.
+    fn transport_request(
+        &self,
+        endpoint: Endpoint,
+        url: String,
+        params: Vec<(&'static str, String)>,
+    ) -> TransportRequest {
+        TransportRequest {
+            endpoint,
+            url,
+            query: params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            headers: self.construct_headers(),
+        }
+    }
+
+    /// Builds the search server URL and query parameters used to geocode an address.
     ///
     /// # Arguments
     ///
     /// * `address` - The address to convert.
+    /// * `near` - The location to search around, the region base coordinates when `None`.
     ///
     /// # Returns
     ///
-    /// A result containing the coordinates or an error.
-    pub fn address_to_coords(
+    /// The request to send to the search server.
+    pub(crate) fn coords_request(
         &self,
         address: &str,
-    ) -> Result<Coordinates, WazeRouteCalculatorError> {
-        let base_coords = WazeRouteCalculator::BASE_COORDS[self.region as usize].1;
+        near: Option<WazeAddressCoordinates>,
+    ) -> TransportRequest {
+        let base_coords = near.unwrap_or(WazeRouteCalculator::BASE_COORDS[self.region as usize].1);
         let get_cord_path = WazeRouteCalculator::COORD_SERVERS[self.region as usize].1;
 
         let url = format!("{}{}", self.base_url, get_cord_path);
         debug!("URL: {}", url);
 
-        let lon_binding = base_coords.lon.to_string();
-        let lat_binding = base_coords.lat.to_string();
-        let params = [
-            ("q", address),
-            ("lang", "eng"),
-            ("lang", "eng"),
-            ("origin", "livemap"),
-            ("lon", lon_binding.as_str()),
-            ("lat", lat_binding.as_str()),
+        let params = vec![
+            ("q", address.to_string()),
+            ("lang", WazeRouteCalculator::LANGUAGE.to_string()),
+            ("lang", WazeRouteCalculator::LANGUAGE.to_string()),
+            ("origin", "livemap".to_string()),
+            ("lon", base_coords.lon.to_string()),
+            ("lat", base_coords.lat.to_string()),
         ];
 
         debug!("params: {:?}", params);
 
-        let client = reqwest::blocking::Client::new();
-        let response = client
-            .get(url)
-            .query(&params)
-            .headers(self.construct_headers())
-            .send()?;
+        self.transport_request(Endpoint::Search, url, params)
+    }
 
-        debug!("Response: {:?}", response);
+    /// Looks up an address in the geocoding cache, if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to look up.
+    ///
+    /// # Returns
+    ///
+    /// The cached coordinates, or `None` on a cache miss.
+    pub(crate) fn cached_coords(&self, address: &str) -> Option<Coordinates> {
+        let coords =
+            self.geocode_cache
+                .as_ref()?
+                .get(address, self.region, WazeRouteCalculator::LANGUAGE);
+        self.observe_cache("geocode", coords.is_some());
+        coords
+    }
 
-        if response.status().is_success() {
-            let address_answer = response.json::<Value>()?;
+    /// Stores the coordinates of an address in the geocoding cache, if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The geocoded address.
+    /// * `coords` - The coordinates of the address.
+    pub(crate) fn cache_coords(&self, address: &str, coords: Coordinates) {
+        if let Some(cache) = &self.geocode_cache {
+            cache.insert(address, self.region, WazeRouteCalculator::LANGUAGE, coords);
+        }
+    }
 
-            if !address_answer.is_array() {
-                error!("Address answer is not an array");
-                return Err(WazeRouteCalculatorError::FailedToGetCoordinates);
+    /// Decides if a request should be retried, logging the failed attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `attempt` - The number of the attempt that just completed, starting at 1.
+    /// * `status` - The answer status, or the error of the request.
+    ///
+    /// # Returns
+    ///
+    /// The delay before the next attempt, or `None` if the request should not be retried.
+    pub(crate) fn retry_delay(
+        &self,
+        attempt: u32,
+        result: &Result<TransportResponse, WazeRouteCalculatorError>,
+    ) -> Option<Duration> {
+        let policy = &self.retry_policy;
+        let reason = match result {
+            Ok(response) if policy.is_retryable_status(response.status) => {
+                format!("status {}", response.status)
             }
+            Err(WazeRouteCalculatorError::NetworkError(e)) if policy.is_retryable_error(e) => {
+                e.to_string()
+            }
+            _ => return None,
+        };
 
-            for answer in address_answer.as_array().unwrap() {
-                if answer.get("city").is_some() {
-                    let mut coords = Coordinates {
-                        latitude: answer["location"]["lat"].as_f64().unwrap_or_default(),
-                        longitude: answer["location"]["lon"].as_f64().unwrap_or_default(),
-                        bound: None,
-                    };
-
-                    if let Some(bound) = answer.get("bounds") {
-                        if bound.is_null() {
-                            return Ok(coords);
-                        }
-
-                        let top = bound.get("top").unwrap().as_f64().unwrap_or_default();
-                        let bottom = bound.get("bottom").unwrap().as_f64().unwrap_or_default();
-                        let left = bound.get("left").unwrap().as_f64().unwrap_or_default();
-                        let right = bound.get("right").unwrap().as_f64().unwrap_or_default();
-
-                        let new_bound = Bound {
-                            top: top.max(bottom),
-                            bottom: top.min(bottom),
-                            left: left.min(right),
-                            right: left.max(right),
-                        };
+        if attempt >= policy.max_attempts {
+            error!(
+                "Attempt {}/{} failed: {}",
+                attempt, policy.max_attempts, reason
+            );
+            return None;
+        }
 
-                        coords.bound = Some(new_bound);
-                    }
-                    return Ok(coords);
-                }
+        let delay = match result {
+            Ok(TransportResponse {
+                retry_after: Some(retry_after),
+                ..
+            }) if *retry_after > policy.max_delay => {
+                error!(
+                    "Attempt {}/{} failed: {}, the server asks to retry in {:?}, after the {:?} maximum delay",
+                    attempt, policy.max_attempts, reason, retry_after, policy.max_delay
+                );
+                return None;
             }
-            error!("Address answer not an array");
-            Err(WazeRouteCalculatorError::FailedToGetCoordinates)
-        } else {
-            error!("Address answer with status: {}", response.status());
-            Err(WazeRouteCalculatorError::FailedToGetCoordinates)
+            Ok(TransportResponse {
+                retry_after: Some(retry_after),
+                ..
+            }) => *retry_after,
+            _ => policy.delay(attempt),
+        };
+        warn!(
+            "Attempt {}/{} failed: {}, retrying in {:?}",
+            attempt, policy.max_attempts, reason, delay
+        );
+        Some(delay)
+    }
+
+    /// Takes a request from the rate limiter budget of an endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The endpoint the request is sent to.
+    ///
+    /// # Returns
+    ///
+    /// How long to wait before sending the request.
+    pub(crate) fn rate_limit_delay(&self, endpoint: Endpoint) -> Duration {
+        self.rate_limiter
+            .as_ref()
+            .map_or(Duration::ZERO, |limiter| limiter.reserve(endpoint))
+    }
+
+    /// Returns the Prometheus metrics of the calculator, if there are some.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Option<&Arc<Metrics>> {
+        self.metrics.as_ref()
+    }
+
+    /// Records a request sent to a Waze server in the metrics, if there are some.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The server the request was sent to.
+    /// * `started` - When the request was sent.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn observe_request(&self, endpoint: Endpoint, started: Instant) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_request(endpoint, started.elapsed());
         }
     }
 
-    fn get_route(&self) -> Result<Vec<WazeResult>, WazeRouteCalculatorError> {
-        let routing_server = WazeRouteCalculator::ROUTING_SERVERS[self.region as usize].1;
-        let from_str = format!(
-            "x:{} y:{}",
-            self.start_coords.unwrap().longitude,
-            self.start_coords.unwrap().latitude
-        );
-        let to_str = format!(
-            "x:{} y:{}",
-            self.end_coords.unwrap().longitude,
-            self.end_coords.unwrap().latitude
-        );
-        let options_str = self
-            .route_options
+    /// Counts the error of a Waze call in the metrics, if there are some.
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - The server of the call.
+    /// * `result` - The result of the call, returned as is.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn observe_result<T>(
+        &self,
+        endpoint: Endpoint,
+        result: Result<T, WazeRouteCalculatorError>,
+    ) -> Result<T, WazeRouteCalculatorError> {
+        #[cfg(feature = "metrics")]
+        if let (Some(metrics), Err(e)) = (&self.metrics, &result) {
+            metrics.observe_error(endpoint, e);
+        }
+        result
+    }
+
+    /// Records a cache lookup in the metrics, if there are some.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn observe_cache(&self, cache: &str, hit: bool) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_cache(cache, hit);
+        }
+    }
+
+    /// Returns the routing answer cache, to read its counters or invalidate it.
+    pub fn route_cache(&self) -> Option<&Arc<RouteCache>> {
+        self.route_cache.as_ref()
+    }
+
+    /// Builds the routing answer cache key of a route request.
+    fn route_cache_key(
+        &self,
+        cache: &RouteCache,
+        request: &RouteRequest,
+        at: i64,
+        n_paths: usize,
+    ) -> RouteCacheKey {
+        RouteCacheKey {
+            base_url: self.base_url.clone(),
+            region: self.region as usize,
+            from: cache.round(&request.origin),
+            to: cache.round(&request.destination),
+            options: self.route_options_string(request),
+            vehicle_type: self.vehicle_type_for(request).to_string().to_owned(),
+            avoid_subscription_roads: self.avoid_subscription_roads_for(request),
+            at,
+            n_paths,
+        }
+    }
+
+    /// Looks up a route in the routing answer cache, if there is one.
+    pub(crate) fn cached_route(
+        &self,
+        request: &RouteRequest,
+        at: i64,
+        n_paths: usize,
+    ) -> Option<Vec<WazeRoute>> {
+        let cache = self.route_cache.as_ref()?;
+        let routes = cache.get(&self.route_cache_key(cache, request, at, n_paths));
+        self.observe_cache("route", routes.is_some());
+        routes
+    }
+
+    /// Stores a route in the routing answer cache, if there is one.
+    pub(crate) fn cache_route(
+        &self,
+        request: &RouteRequest,
+        at: i64,
+        n_paths: usize,
+        routes: &[WazeRoute],
+    ) {
+        if let Some(cache) = &self.route_cache {
+            let key = self.route_cache_key(cache, request, at, n_paths);
+            cache.insert(key, routes.to_vec());
+        }
+    }
+
+    /// Returns the vehicle type of a request, falling back to the calculator one.
+    fn vehicle_type_for(&self, request: &RouteRequest) -> VehicleType {
+        request.vehicle_type.unwrap_or(self.vehicle_type)
+    }
+
+    /// Returns whether a request avoids subscription roads, falling back to the calculator setting.
+    fn avoid_subscription_roads_for(&self, request: &RouteRequest) -> bool {
+        request
+            .avoid_subscription_roads
+            .unwrap_or(self.avoid_subscription_roads)
+    }
+
+    /// Builds the `options` query parameter of a request, applying its overrides to the
+    /// calculator route options.
+    fn route_options_string(&self, request: &RouteRequest) -> String {
+        let mut options = request
+            .options
+            .clone()
+            .unwrap_or_else(|| self.route_options.clone());
+        if let Some(value) = request.avoid_toll_roads {
+            options.avoid_toll_roads = Some(value);
+        }
+        if let Some(value) = request.avoid_ferries {
+            options.avoid_ferries = Some(value);
+        }
+
+        options.to_string()
+    }
+
+    /// Parses the search server answer into the ranked list of candidate addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `address_answer` - The JSON answer returned by the search server.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the candidate addresses, with normalized bounds, or an error.
+    pub(crate) fn parse_address_answer(
+        address_answer: Value,
+    ) -> Result<WazeAddressAnswer, WazeRouteCalculatorError> {
+        if !address_answer.is_array() {
+            error!("Address answer is not an array");
+            return Err(WazeRouteCalculatorError::InvalidResponse(format!(
+                "address answer is not an array: {}",
+                truncate(&address_answer.to_string())
+            )));
+        }
+
+        let mut addresses: WazeAddressAnswer = serde_json::from_value(address_answer)?;
+        for address in addresses.iter_mut() {
+            address.bounds = address.bounds.map(|bound| bound.normalized());
+        }
+
+        Ok(addresses)
+    }
+
+    /// Parses the search server answer into coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `address_answer` - The JSON answer returned by the search server.
+    /// * `address` - The address that was looked up.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the coordinates of the first address with a city or an error.
+    pub(crate) fn parse_coords_answer(
+        address_answer: Value,
+        address: &str,
+    ) -> Result<Coordinates, WazeRouteCalculatorError> {
+        WazeRouteCalculator::parse_address_answer(address_answer)?
             .iter()
-            .map(|(opt, value)| format!("{}:{}", opt, value))
-            .collect::<Vec<_>>()
-            .join(",");
+            .find(|address| address.city.is_some())
+            .map(Coordinates::from)
+            .ok_or_else(|| {
+                error!("No address with a city found for {}", address);
+                WazeRouteCalculatorError::NoResults(address.to_string())
+            })
+    }
+
+    /// Parses the search server answer of a reverse geocoding query.
+    ///
+    /// # Arguments
+    ///
+    /// * `address_answer` - The JSON answer returned by the search server.
+    /// * `coords` - The coordinates that were looked up.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the address nearest to `coords` or an error.
+    pub(crate) fn parse_reverse_answer(
+        address_answer: Value,
+        coords: &Coordinates,
+    ) -> Result<WazeAddress, WazeRouteCalculatorError> {
+        WazeRouteCalculator::parse_address_answer(address_answer)?
+            .into_iter()
+            .min_by(|a, b| {
+                let a = haversine_distance(coords, &Coordinates::from(a.location));
+                let b = haversine_distance(coords, &Coordinates::from(b.location));
+                a.total_cmp(&b)
+            })
+            .ok_or_else(|| {
+                let query = format!("{}, {}", coords.latitude, coords.longitude);
+                error!("No address found near {}", query);
+                WazeRouteCalculatorError::NoResults(query)
+            })
+    }
+
+    /// Builds the routing server URL and query parameters for a route between two coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, with its option overrides.
+    /// * `at` - The departure offset from now in minutes.
+    /// * `n_paths` - The number of alternative routes to ask for.
+    ///
+    /// # Returns
+    ///
+    /// The request to send to the routing server.
+    pub(crate) fn route_request(
+        &self,
+        request: &RouteRequest,
+        at: i64,
+        n_paths: usize,
+    ) -> TransportRequest {
+        let routing_server = WazeRouteCalculator::ROUTING_SERVERS[self.region as usize].1;
+        let (from, to) = (&request.origin, &request.destination);
+        let from_str = format!("x:{} y:{}", from.longitude, from.latitude);
+        let to_str = format!("x:{} y:{}", to.longitude, to.latitude);
+        let options_str = self.route_options_string(request);
+        let vehicle_type = self.vehicle_type_for(request);
 
-        //TODO: Handle nPaths and time_delta
         let mut params = vec![
-            ("from", from_str.as_str()),
-            ("to", to_str.as_str()),
-            ("at", "0"),
-            ("returnJSON", "true"),
-            ("returnGeometries", "true"),
-            ("returnInstructions", "true"),
-            ("timeout", "60000"),
-            ("nPaths", "1"),
-            ("options", &options_str),
+            ("from", from_str),
+            ("to", to_str),
+            ("at", at.to_string()),
+            ("returnJSON", "true".to_string()),
+            ("returnGeometries", "true".to_string()),
+            ("returnInstructions", "true".to_string()),
+            ("timeout", "60000".to_string()),
+            ("nPaths", n_paths.to_string()),
+            ("options", options_str),
         ];
 
-        if self.vehicle_type != VehicleType::CAR {
-            params.push(("vehicleType", self.vehicle_type.to_string()));
+        if vehicle_type != VehicleType::CAR {
+            params.push(("vehicleType", vehicle_type.to_string().to_owned()));
         }
 
-        if !self.avoid_subscription_roads {
-            params.push(("subscription", "*"));
+        if !self.avoid_subscription_roads_for(request) {
+            params.push(("subscription", "*".to_string()));
         }
 
         debug!("params: {:?}", params);
@@ -375,47 +1057,130 @@ impl WazeRouteCalculator {
         let url = format!("{}{}", self.base_url, routing_server);
         debug!("URL: {}", url);
 
-        let client = reqwest::blocking::Client::new();
-        let query_res = client
-            .get(url)
-            .query(&params)
-            .headers(self.construct_headers())
-            .send()?;
-
-        debug!("Response: {:?}", query_res);
-
-        if query_res.status().is_success() {
-            let waze_route_answer: Value = query_res.json()?;
-
-            if waze_route_answer.get("error").is_none() {
-                if let Some(response) = waze_route_answer.get("response") {
-                    if let Some(alternatives) = response.get("alternatives") {
-                        return Ok(alternatives
-                            .as_array()
-                            .unwrap()
-                            .iter()
-                            .map(|x| serde_json::from_value(x.clone()).unwrap())
-                            .collect());
-                    }
+        self.transport_request(Endpoint::Routing, url, params)
+    }
 
-                    if let Some(results) = response.get("results") {
-                        Ok(serde_json::from_value(results.clone())?)
-                    } else {
-                        error!("'results' field not found");
-                        Err(WazeRouteCalculatorError::FailedToGetRoute)
-                    }
-                } else {
-                    error!("'response' field not found");
-                    Err(WazeRouteCalculatorError::FailedToGetRoute)
+    /// Checks the answer of a Waze server and parses its JSON body.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request that was sent.
+    /// * `response` - The answer received.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the JSON body, or an error with the status, URL, query and
+    /// truncated body if the answer is an HTML page or has a non-2xx status.
+    pub(crate) fn parse_response(
+        request: &TransportRequest,
+        response: TransportResponse,
+    ) -> Result<Value, WazeRouteCalculatorError> {
+        let is_html = response.body.trim_start().starts_with('<');
+        if is_html || !response.is_success() {
+            error!(
+                "{:?} answer with status {}{}",
+                request.endpoint,
+                response.status,
+                if is_html { " (HTML)" } else { "" }
+            );
+
+            let (status, url, query, body) = (
+                response.status,
+                request.url.clone(),
+                request.query_string(),
+                truncate(&response.body),
+            );
+            return Err(if is_html {
+                WazeRouteCalculatorError::HtmlResponse {
+                    status,
+                    url,
+                    query,
+                    body,
                 }
             } else {
-                let error = waze_route_answer["error"].as_str().unwrap().to_string();
-                error!("Waze Error: {}", error);
-                Err(WazeRouteCalculatorError::WazeApiError(error))
+                WazeRouteCalculatorError::HttpStatus {
+                    status,
+                    url,
+                    query,
+                    body,
+                }
+            });
+        }
+
+        response.json()
+    }
+
+    /// Parses the routing server answer into routes.
+    ///
+    /// Waze returns either a single `response` object (or a list of them), or an `alternatives`
+    /// array with one `response` per alternative route when more than one path was asked for.
+    ///
+    /// # Arguments
+    ///
+    /// * `waze_route_answer` - The JSON answer returned by the routing server.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the routes or an error.
+    pub(crate) fn parse_route_answer(
+        waze_route_answer: &Value,
+    ) -> Result<Vec<WazeRoute>, WazeRouteCalculatorError> {
+        if let Some(error) = waze_route_answer.get("error") {
+            let error = match error {
+                Value::String(error) => error.clone(),
+                error => error.to_string(),
+            };
+            error!("Waze Error: {}", error);
+            return Err(WazeRouteCalculatorError::WazeApiError(error));
+        }
+
+        // The route geometry is sent next to each `response`, not inside it.
+        let responses: Vec<(Value, Option<&Value>)> = if let Some(alternatives) =
+            waze_route_answer.get("alternatives")
+        {
+            let Some(alternatives) = alternatives.as_array() else {
+                error!("'alternatives' field is not an array");
+                return Err(WazeRouteCalculatorError::InvalidResponse(format!(
+                    "'alternatives' is not an array: {}",
+                    alternatives
+                )));
+            };
+
+            alternatives
+                .iter()
+                .map(|alternative| (alternative["response"].clone(), alternative.get("coords")))
+                .collect()
+        } else if let Some(response) = waze_route_answer.get("response") {
+            match response {
+                Value::Array(responses) => responses.iter().map(|x| (x.clone(), None)).collect(),
+                _ => vec![(response.clone(), waze_route_answer.get("coords"))],
             }
         } else {
-            Err(WazeRouteCalculatorError::FailedToGetRoute)
+            error!("'response' field not found");
+            return Err(WazeRouteCalculatorError::FailedToGetRoute);
+        };
+
+        if responses.is_empty() || responses.iter().any(|(x, _)| x.get("results").is_none()) {
+            error!("'results' field not found");
+            return Err(WazeRouteCalculatorError::FailedToGetRoute);
         }
+
+        responses
+            .into_iter()
+            .enumerate()
+            .map(|(index, (response, coords))| {
+                let invalid = |e: serde_json::Error| {
+                    error!("Failed to parse route {}: {}", index, e);
+                    WazeRouteCalculatorError::InvalidResponse(format!("route {}: {}", index, e))
+                };
+
+                let mut route: WazeRoute = serde_json::from_value(response).map_err(invalid)?;
+                if let Some(coords) = coords {
+                    route.coords = Vec::<WazeCoord>::deserialize(coords).map_err(invalid)?;
+                }
+                Ok(route)
+            })
+            .collect()
     }
 
     /// Calculates the route time and distance based on the provided results.
@@ -461,142 +1226,1431 @@ impl WazeRouteCalculator {
             target > min && target < max
         }
 
-        let (time, distance) = results
-            .iter()
-            .fold((0, 0), |(mut time, mut distance), segment| {
-                if stop_at_bounds {
-                    if let Some(path) = &segment.path {
-                        let x = path.x;
-                        let y = path.y;
-                        if (between(x, start_bounds.left, start_bounds.right)
-                            || between(x, end_bounds.left, end_bounds.right))
-                            && (between(y, start_bounds.bottom, start_bounds.top)
-                                || between(y, end_bounds.bottom, end_bounds.top))
-                        {
-                            return (time, distance);
+        let (time, distance) =
+            results
+                .iter()
+                .fold((0i64, 0i64), |(mut time, mut distance), segment| {
+                    if stop_at_bounds {
+                        if let Some(path) = &segment.path {
+                            let x = path.x;
+                            let y = path.y;
+                            if (between(x, start_bounds.left, start_bounds.right)
+                                || between(x, end_bounds.left, end_bounds.right))
+                                && (between(y, start_bounds.bottom, start_bounds.top)
+                                    || between(y, end_bounds.bottom, end_bounds.top))
+                            {
+                                return (time, distance);
+                            }
                         }
                     }
-                }
 
-                if real_time {
-                    time += segment.cross_time;
-                } else {
-                    time += segment.cross_time_without_real_time;
-                }
-                distance += segment.length;
+                    if real_time {
+                        time = time.saturating_add(segment.cross_time);
+                    } else {
+                        time = time.saturating_add(segment.cross_time_without_real_time);
+                    }
+                    distance = distance.saturating_add(segment.length);
 
-                (time, distance)
-            });
+                    (time, distance)
+                });
 
         let route_time = time as f64 / 60.0;
         let route_distance = distance as f64 / 1000.0;
         (route_time, route_distance)
     }
 
-    /// Calculates the best route info by calling `get_route` and using `add_up_route` to calculate the route time and distance.
+    /// Sums up the route segments into a `RouteSummary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `route` - The route returned by `get_route`.
+    /// * `departure_time` - The departure time the route was calculated for.
     ///
     /// # Returns
     ///
-    /// A result containing a tuple with the route time in minutes and the route distance in kilometers, or an error.
-    pub fn calculate_route(&self) -> Result<(std::time::Duration, f64), WazeRouteCalculatorError> {
-        let route = self.get_route()?;
+    /// A `RouteSummary` with the realtime and free-flow durations and the distance of the route.
+    pub(crate) fn summarize_route(
+        &self,
+        route: WazeRoute,
+        departure_time: SystemTime,
+    ) -> RouteSummary {
+        let (time, free_flow_time, distance) = route.results.iter().fold(
+            (0i64, 0i64, 0i64),
+            |(time, free_flow_time, distance), segment| {
+                (
+                    time.saturating_add(segment.cross_time),
+                    free_flow_time.saturating_add(segment.cross_time_without_real_time),
+                    distance.saturating_add(segment.length),
+                )
+            },
+        );
 
-        let (route_time, route_distance) = self.add_up_route(&route, true, false);
+        let duration = Duration::from_secs(time.max(0) as u64);
+        let free_flow_duration = Duration::from_secs(free_flow_time.max(0) as u64);
 
-        debug!("Route time: {}", route_time);
-        debug!("Route distance: {}", route_distance);
+        debug!("Route time: {:?}", duration);
+        debug!("Route distance: {}", distance);
 
-        Ok((
-            std::time::Duration::from_secs(route_time as u64 * 60),
-            route_distance,
-        ))
-    }
-}
+        let maneuvers = parse_maneuvers(&route);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::waze_route_calculator::WazeResult;
-    use crate::waze_structs::WazePath;
+        let geometry = if route.coords.is_empty() {
+            route
+                .results
+                .iter()
+                .filter_map(|segment| segment.path)
+                .map(|path| WazeCoord {
+                    x: path.x,
+                    y: path.y,
+                })
+                .collect()
+        } else {
+            route.coords
+        };
 
-    #[test]
-    fn test_vehicle_type_to_string() {
-        let vehicle_type = VehicleType::TAXI;
-        pretty_assertions::assert_eq!(vehicle_type.to_string(), "TAXI");
+        RouteSummary {
+            route_name: route
+                .route_name
+                .or(route.short_route_name)
+                .unwrap_or_default(),
+            duration,
+            free_flow_duration,
+            traffic_delay: duration.saturating_sub(free_flow_duration),
+            distance: distance.max(0) as u64,
+            departure_time,
+            arrival_time: departure_time
+                .checked_add(duration)
+                .unwrap_or(departure_time),
+            segments: route.results,
+            geometry,
+            maneuvers,
+        }
+    }
 
-        let vehicle_type = VehicleType::MOTORCYCLE;
-        pretty_assertions::assert_eq!(vehicle_type.to_string(), "MOTORCYCLE");
+    /// Converts the routes returned by the routing server into route summaries.
+    ///
+    /// # Arguments
+    ///
+    /// * `routes` - The routes returned by `get_route`.
+    /// * `departure_time` - The departure time the routes were calculated for.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `RouteSummary`, in the order Waze ranked them.
+    pub(crate) fn summarize_routes(
+        &self,
+        routes: Vec<WazeRoute>,
+        departure_time: SystemTime,
+    ) -> Vec<RouteSummary> {
+        routes
+            .into_iter()
+            .map(|route| self.summarize_route(route, departure_time))
+            .collect()
+    }
 
-        let vehicle_type = VehicleType::CAR;
-        pretty_assertions::assert_eq!(vehicle_type.to_string(), "");
+    /// Returns the best of the routes returned by the routing server as a `RouteSummary`.
+    ///
+    /// # Arguments
+    ///
+    /// * `routes` - The routes returned by `get_route`.
+    /// * `departure_time` - The departure time the routes were calculated for.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `RouteSummary` of the first route, or an error if there is none.
+    pub(crate) fn summarize_best_route(
+        &self,
+        routes: Vec<WazeRoute>,
+        departure_time: SystemTime,
+    ) -> Result<RouteSummary, WazeRouteCalculatorError> {
+        let route = routes.into_iter().next().ok_or_else(|| {
+            error!("No route found");
+            WazeRouteCalculatorError::FailedToGetRoute
+        })?;
+
+        Ok(self.summarize_route(route, departure_time))
     }
 
-    #[test]
-    fn test_address_to_coords() {
-        let opts = mockito::ServerOpts {
-            host: "127.0.0.1",
-            port: 1234,
-            ..Default::default()
-        };
-        let mut server = mockito::Server::new_with_opts(opts);
+    /// Returns the start and end coordinates.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `(start, end)` coordinates, or an error if one of them is not set.
+    pub(crate) fn route_coords(
+        &self,
+    ) -> Result<(Coordinates, Coordinates), WazeRouteCalculatorError> {
+        let start = self
+            .start_coords
+            .ok_or(WazeRouteCalculatorError::MissingCoordinates("start"))?;
+        let end = self
+            .end_coords
+            .ok_or(WazeRouteCalculatorError::MissingCoordinates("end"))?;
 
-        // Use one of these addresses to configure your client
-        let url = server.url() + "/";
+        Ok((start, end))
+    }
 
-        let mock  = server.mock("GET", "/SearchServer/mozi")
-            .with_status(200)
-            .with_header("content-type", "application/json")
-            .with_body(r#"[{"bounds":null,"businessName":"Address","city":"Detroit","countryName":"United States","location":{"lat":12.34,"lon":56.78},"name":"Address","number":"5512","provider":"waze","segmentId":23621747,"state":null,"stateName":"Michigan","street":"Beaubien St","streetId":1601804},{"bounds":null,"businessName":"Address","city":"Dayton","countryName":"United States","location":{"lat":39.763481974,"lon":-84.184411227},"name":"Address","number":"418","provider":"waze","segmentId":0,"state":"Ohio","stateName":"Ohio","street":"E 1st St","streetId":2417464},{"bounds":null,"businessName":null,"city":"Aberdeen Proving Ground","countryName":"United States","location":{"lat":39.47930145263672,"lon":-76.17952728271484},"name":"Test Hwy, Aberdeen Proving Ground, MD","number":null,"provider":"waze","segmentId":-1,"state":"MD","stateName":"Maryland","street":"Test Hwy","streetId":61686875},{"bounds":null,"businessName":null,"city":null,"countryName":"United States","location":{"lat":39.46147155761719,"lon":-76.19284057617188},"name":"Test Hwy, MD","number":null,"provider":"waze","segmentId":-1,"state":"MD","stateName":"Maryland","street":"Test Hwy","streetId":4704325},{"bounds":null,"businessName":null,"city":"Union","countryName":"United States","location":{"lat":40.686431884765625,"lon":-74.26087188720703},"name":"Andress Ter, Union, NJ","number":null,"provider":"waze","segmentId":-1,"state":"NJ","stateName":"New Jersey","street":"Andress Ter","streetId":2011162}]"#)
-            .match_query(mockito::Matcher::Any)
-            .create();
+    /// Returns the ordered legs of the trip, from the start through the via points to the end.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `(from, to)` coordinate pairs, or an error if the start or end is not set.
+    pub(crate) fn legs(&self) -> Result<Vec<(Coordinates, Coordinates)>, WazeRouteCalculatorError> {
+        let (start, end) = self.route_coords()?;
+        let waypoints = std::iter::once(start)
+            .chain(self.via_coords.iter().copied())
+            .chain(std::iter::once(end))
+            .collect::<Vec<_>>();
+
+        Ok(waypoints.windows(2).map(|x| (x[0], x[1])).collect())
+    }
+}
 
-        let calculator = WazeRouteCalculator::builder()
-            .set_region(Region::US)
-            .set_vehicle_type(VehicleType::CAR)
-            .set_base_url(url.as_str())
-            .build();
+#[cfg(feature = "blocking")]
+impl WazeRouteCalculator {
+    /// Sets the start and end coordinates based on the provided addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_address` - The starting address.
+    /// * `end_address` - The ending address.
+    ///
+    /// # Returns
+    ///
+    /// A result containing a mutable reference to the `WazeRouteCalculator` instance or an error.
+    pub fn set_address(
+        &mut self,
+        start_address: &str,
+        end_address: &str,
+    ) -> Result<&mut Self, WazeRouteCalculatorError> {
+        let start_coords = self.address_to_coords(start_address)?;
+        let end_coords = self.address_to_coords(end_address)?;
 
-        let result = calculator.address_to_coords("Test Address");
+        debug!(
+            "Start coordinates: {}, {}",
+            start_coords.latitude, start_coords.longitude
+        );
 
-        mock.assert();
+        debug!(
+            "End coordinates: {}, {}",
+            end_coords.latitude, end_coords.longitude
+        );
 
-        pretty_assertions::assert_eq!(result.is_ok(), true);
-        let coords = result.unwrap();
-        pretty_assertions::assert_eq!(coords.latitude, 12.34);
-        pretty_assertions::assert_eq!(coords.longitude, 56.78);
-    }
+        self.start_coords = Some(start_coords);
+        self.end_coords = Some(end_coords);
 
-    fn create_mock_waze_result() -> WazeResult {
-        WazeResult {
-            path: Some(WazePath {
-                segment_id: 0,
-                node_id: 0,
-                x: 0.0,
-                y: 0.0,
-                direction: false,
-            }),
-            cross_time: 120,
-            cross_time_without_real_time: 100,
-            length: 1000,
-            ..Default::default()
-        }
+        Ok(self)
     }
 
-    #[test]
-    fn test_add_up_route() {
-        let calculator = WazeRouteCalculator::builder()
-            .set_region(Region::US)
-            .set_vehicle_type(VehicleType::CAR)
-            .build();
-
-        let results = vec![create_mock_waze_result()];
-
-        let (route_time, route_distance) = calculator.add_up_route(&results, true, false);
-        pretty_assertions::assert_eq!(route_time, 2.0); // 120 seconds / 60 = 2 minutes
-        pretty_assertions::assert_eq!(route_distance, 1.0); // 1000 meters / 1000 = 1 kilometer
+    /// Converts an address to coordinates.
+    ///
+    /// Coordinate strings, like `"32.08,34.78"` or DMS notation, are parsed locally
+    /// without calling the search server.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to convert.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the coordinates or an error.
+    pub fn address_to_coords(
+        &self,
+        address: &str,
+    ) -> Result<Coordinates, WazeRouteCalculatorError> {
+        if let Some(coords) = crate::helpers::parse_coordinates(address) {
+            debug!("Address is already coordinates: {:?}", coords);
+            return Ok(coords);
+        }
+
+        if let Some(coords) = self.cached_coords(address) {
+            return Ok(coords);
+        }
+
+        let address_answer = self.search(address, None)?;
+        let coords = WazeRouteCalculator::parse_coords_answer(address_answer, address)?;
+        self.cache_coords(address, coords);
+
+        Ok(coords)
+    }
+
+    /// Geocodes an address into all the candidate addresses returned by Waze.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to geocode.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the candidate addresses in the order Waze ranked them, or an error.
+    pub fn geocode(&self, address: &str) -> Result<WazeAddressAnswer, WazeRouteCalculatorError> {
+        let address_answer = self.search(address, None)?;
+
+        WazeRouteCalculator::parse_address_answer(address_answer)
+    }
+
+    /// Converts coordinates to the nearest address.
+    ///
+    /// # Arguments
+    ///
+    /// * `coords` - The coordinates to convert.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the nearest address or an error.
+    pub fn coords_to_address(
+        &self,
+        coords: &Coordinates,
+    ) -> Result<WazeAddress, WazeRouteCalculatorError> {
+        let query = format!("{}, {}", coords.latitude, coords.longitude);
+        let address_answer = self.search(&query, Some(coords.into()))?;
+
+        WazeRouteCalculator::parse_reverse_answer(address_answer, coords)
+    }
+
+    /// Sends a request through the transport, respecting the rate limiter and retrying it
+    /// according to the retry policy.
+    fn send(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, WazeRouteCalculatorError> {
+        let mut attempt = 1;
+
+        loop {
+            std::thread::sleep(self.rate_limit_delay(request.endpoint));
+
+            debug!("Attempt {}: {}", attempt, request.url);
+            let started = Instant::now();
+            let result = self.transport.send(request);
+            self.observe_request(request.endpoint, started);
+
+            let Some(delay) = self.retry_delay(attempt, &result) else {
+                return result;
+            };
+
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    fn search(
+        &self,
+        address: &str,
+        near: Option<WazeAddressCoordinates>,
+    ) -> Result<Value, WazeRouteCalculatorError> {
+        let request = self.coords_request(address, near);
+
+        let result = self
+            .send(&request)
+            .and_then(|response| WazeRouteCalculator::parse_response(&request, response));
+
+        self.observe_result(Endpoint::Search, result)
+    }
+
+    fn get_route(
+        &self,
+        route_request: &RouteRequest,
+        at: i64,
+        n_paths: usize,
+    ) -> Result<Vec<WazeRoute>, WazeRouteCalculatorError> {
+        if let Some(routes) = self.cached_route(route_request, at, n_paths) {
+            return Ok(routes);
+        }
+
+        let request = self.route_request(route_request, at, n_paths);
+
+        let result = self
+            .send(&request)
+            .and_then(|query_res| WazeRouteCalculator::parse_response(&request, query_res))
+            .and_then(|waze_route_answer| {
+                WazeRouteCalculator::parse_route_answer(&waze_route_answer)
+            });
+
+        let routes = self.observe_result(Endpoint::Routing, result)?;
+        self.cache_route(route_request, at, n_paths, &routes);
+
+        Ok(routes)
+    }
+
+    /// Calculates the best route of a `RouteRequest`, without changing the calculator.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The route query, with its option overrides.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `RouteSummary` of the best route, or an error.
+    pub fn route(&self, request: &RouteRequest) -> Result<RouteSummary, WazeRouteCalculatorError> {
+        let now = SystemTime::now();
+        let departure_time = request.departure_time.unwrap_or(self.departure_time);
+        let departure = departure_time.to_system_time(now)?;
+        let routes = self.get_route(request, departure_time.to_minutes_offset(now), 1)?;
+
+        self.summarize_best_route(routes, departure)
+    }
+
+    /// Calculates the best route by calling `get_route` and summing up its segments.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `RouteSummary` of the best route, or an error.
+    pub fn calculate_route(&self) -> Result<RouteSummary, WazeRouteCalculatorError> {
+        let (start, end) = self.route_coords()?;
+
+        self.route(&RouteRequest::new(start, end))
+    }
+
+    /// Calculates up to `n_paths` alternative routes, each with its own time, distance, name and segments.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_paths` - The number of alternative routes to ask Waze for.
+    ///
+    /// # Returns
+    ///
+    /// A result containing a `RouteSummary` for each route alternative, or an error.
+    pub fn calculate_routes(
+        &self,
+        n_paths: usize,
+    ) -> Result<Vec<RouteSummary>, WazeRouteCalculatorError> {
+        let now = SystemTime::now();
+        let (start, end) = self.route_coords()?;
+        let departure = self.departure_time.to_system_time(now)?;
+        let routes = self.get_route(
+            &RouteRequest::new(start, end),
+            self.departure_time.to_minutes_offset(now),
+            n_paths,
+        )?;
+
+        Ok(self.summarize_routes(routes, departure))
+    }
+
+    /// Sets the via points based on the provided addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses` - The ordered intermediate stops.
+    ///
+    /// # Returns
+    ///
+    /// A result containing a mutable reference to the `WazeRouteCalculator` instance or an error.
+    pub fn set_via_addresses(
+        &mut self,
+        addresses: &[&str],
+    ) -> Result<&mut Self, WazeRouteCalculatorError> {
+        self.via_coords = addresses
+            .iter()
+            .map(|address| self.address_to_coords(address))
+            .collect::<Result<_, _>>()?;
+
+        debug!("Via coordinates: {:?}", self.via_coords);
+
+        Ok(self)
+    }
+
+    /// Calculates the route from the start through the via points to the end.
+    ///
+    /// Each leg is a separate routing request, departing when the previous leg arrives.
+    ///
+    /// # Returns
+    ///
+    /// A result containing the `MultiLegRoute` with the per-leg breakdown and totals, or an error.
+    pub fn calculate_route_via(&self) -> Result<MultiLegRoute, WazeRouteCalculatorError> {
+        let now = SystemTime::now();
+        let mut departure_time = self.departure_time.to_system_time(now)?;
+        let mut legs = vec![];
+
+        for (from, to) in self.legs()? {
+            let at = DepartureTime::At(departure_time).to_minutes_offset(now);
+            let routes = self.get_route(&RouteRequest::new(from, to), at, 1)?;
+            let leg = self.summarize_best_route(routes, departure_time)?;
+
+            departure_time = leg.arrival_time;
+            legs.push(leg);
+        }
+
+        Ok(MultiLegRoute::from(legs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::parse_coordinates;
+    #[cfg(feature = "blocking")]
+    use crate::rate_limit::RateLimit;
+    #[cfg(feature = "blocking")]
+    use crate::test_fixtures::route_answer;
+    use crate::waze_route_calculator::WazeResult;
+    use crate::waze_structs::WazePath;
+
+    #[test]
+    fn test_vehicle_type_to_string() {
+        let vehicle_type = VehicleType::TAXI;
+        pretty_assertions::assert_eq!(vehicle_type.to_string(), "TAXI");
+
+        let vehicle_type = VehicleType::MOTORCYCLE;
+        pretty_assertions::assert_eq!(vehicle_type.to_string(), "MOTORCYCLE");
+
+        let vehicle_type = VehicleType::CAR;
+        pretty_assertions::assert_eq!(vehicle_type.to_string(), "");
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_address_to_coords() {
+        let opts = mockito::ServerOpts {
+            host: "127.0.0.1",
+            port: 1234,
+            ..Default::default()
+        };
+        let mut server = mockito::Server::new_with_opts(opts);
+
+        // Use one of these addresses to configure your client
+        let url = server.url() + "/";
+
+        let mock  = server.mock("GET", "/SearchServer/mozi")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"bounds":null,"businessName":"Address","city":"Detroit","countryName":"United States","location":{"lat":12.34,"lon":56.78},"name":"Address","number":"5512","provider":"waze","segmentId":23621747,"state":null,"stateName":"Michigan","street":"Beaubien St","streetId":1601804},{"bounds":null,"businessName":"Address","city":"Dayton","countryName":"United States","location":{"lat":39.763481974,"lon":-84.184411227},"name":"Address","number":"418","provider":"waze","segmentId":0,"state":"Ohio","stateName":"Ohio","street":"E 1st St","streetId":2417464},{"bounds":null,"businessName":null,"city":"Aberdeen Proving Ground","countryName":"United States","location":{"lat":39.47930145263672,"lon":-76.17952728271484},"name":"Test Hwy, Aberdeen Proving Ground, MD","number":null,"provider":"waze","segmentId":-1,"state":"MD","stateName":"Maryland","street":"Test Hwy","streetId":61686875},{"bounds":null,"businessName":null,"city":null,"countryName":"United States","location":{"lat":39.46147155761719,"lon":-76.19284057617188},"name":"Test Hwy, MD","number":null,"provider":"waze","segmentId":-1,"state":"MD","stateName":"Maryland","street":"Test Hwy","streetId":4704325},{"bounds":null,"businessName":null,"city":"Union","countryName":"United States","location":{"lat":40.686431884765625,"lon":-74.26087188720703},"name":"Andress Ter, Union, NJ","number":null,"provider":"waze","segmentId":-1,"state":"NJ","stateName":"New Jersey","street":"Andress Ter","streetId":2011162}]"#)
+            .match_query(mockito::Matcher::Any)
+            .create();
+
+        let calculator = WazeRouteCalculator::builder()
+            .set_region(Region::US)
+            .set_vehicle_type(VehicleType::CAR)
+            .set_base_url(url.as_str())
+            .build();
+
+        let result = calculator.address_to_coords("Test Address");
+
+        mock.assert();
+
+        pretty_assertions::assert_eq!(result.is_ok(), true);
+        let coords = result.unwrap();
+        pretty_assertions::assert_eq!(coords.latitude, 12.34);
+        pretty_assertions::assert_eq!(coords.longitude, 56.78);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_geocode() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let mock = server
+            .mock("GET", "/SearchServer/mozi")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"bounds":null,"businessName":null,"city":null,"countryName":"United States","location":{"lat":39.8,"lon":-89.6},"name":"Springfield","provider":"waze","segmentId":-1,"streetId":0},{"bounds":{"top":39.7,"bottom":39.9,"left":-89.5,"right":-89.7},"businessName":null,"city":"Springfield","countryName":"United States","location":{"lat":39.78,"lon":-89.65},"name":"Springfield, IL","provider":"waze","segmentId":-1,"stateName":"Illinois","streetId":0},{"bounds":null,"city":"Springfield","countryName":"United States","location":{"lat":37.2,"lon":-93.29},"name":"Springfield, MO","provider":"waze"}]"#)
+            .match_query(mockito::Matcher::UrlEncoded("q".into(), "Springfield".into()))
+            .expect(2)
+            .create();
+
+        let calculator = WazeRouteCalculator::builder()
+            .set_region(Region::US)
+            .set_base_url(url.as_str())
+            .build();
+
+        let addresses = calculator.geocode("Springfield").unwrap();
+        pretty_assertions::assert_eq!(addresses.len(), 3);
+        pretty_assertions::assert_eq!(addresses[2].name, "Springfield, MO");
+        pretty_assertions::assert_eq!(
+            addresses[1].bounds,
+            Some(Bound {
+                top: 39.9,
+                bottom: 39.7,
+                left: -89.7,
+                right: -89.5,
+            })
+        );
+
+        let coords = calculator.address_to_coords("Springfield").unwrap();
+        mock.assert();
+
+        pretty_assertions::assert_eq!(coords.latitude, 39.78);
+        pretty_assertions::assert_eq!(coords.bound, addresses[1].bounds);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_coords_to_address() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let mock = server
+            .mock("GET", "/il-SearchServer/mozi")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"bounds":null,"city":"Tel Aviv","countryName":"Israel","location":{"lat":32.1,"lon":34.8},"name":"Ibn Gabirol St 1, Tel Aviv","number":"1","street":"Ibn Gabirol St"},{"bounds":null,"city":"Tel Aviv","countryName":"Israel","location":{"lat":32.0801,"lon":34.7801},"name":"Dizengoff St 50, Tel Aviv","number":"50","street":"Dizengoff St"}]"#)
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("lat".into(), "32.08".into()),
+                mockito::Matcher::UrlEncoded("lon".into(), "34.78".into()),
+            ]))
+            .create();
+
+        let calculator = WazeRouteCalculator::builder()
+            .set_region(Region::IL)
+            .set_base_url(url.as_str())
+            .build();
+
+        let coords = Coordinates {
+            latitude: 32.08,
+            longitude: 34.78,
+            bound: None,
+        };
+        let address = calculator.coords_to_address(&coords).unwrap();
+
+        mock.assert();
+
+        pretty_assertions::assert_eq!(address.street.as_deref(), Some("Dizengoff St"));
+        pretty_assertions::assert_eq!(address.number.as_deref(), Some("50"));
+        pretty_assertions::assert_eq!(address.city.as_deref(), Some("Tel Aviv"));
+        pretty_assertions::assert_eq!(address.country_name.as_deref(), Some("Israel"));
+    }
+
+    #[test]
+    fn test_parse_coordinates() {
+        let round = |x: f64| (x * 1e6).round() / 1e6;
+        let parse =
+            |text: &str| parse_coordinates(text).map(|x| (round(x.latitude), round(x.longitude)));
+
+        pretty_assertions::assert_eq!(parse("32.08,34.78"), Some((32.08, 34.78)));
+        pretty_assertions::assert_eq!(parse(" -35.281  149.128 "), Some((-35.281, 149.128)));
+        pretty_assertions::assert_eq!(parse("32°04'48\"N 34°46'48\"E"), Some((32.08, 34.78)));
+        pretty_assertions::assert_eq!(parse("34°46'48\"E, 32°04'48\"N"), Some((32.08, 34.78)));
+        pretty_assertions::assert_eq!(parse("S35 16.86 E149 7.68"), Some((-35.281, 149.128)));
+        pretty_assertions::assert_eq!(parse("12 34"), None);
+        pretty_assertions::assert_eq!(parse("Tel Aviv, Israel"), None);
+        pretty_assertions::assert_eq!(parse("95.0,34.78"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_set_address_with_coordinates() {
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url("http://127.0.0.1:9/")
+            .build();
+
+        calculator
+            .set_address("32.08,34.78", "31°46'4.8\"N 35°12'50.4\"E")
+            .unwrap();
+
+        pretty_assertions::assert_eq!(
+            calculator.start_coords,
+            Some(Coordinates::new(32.08, 34.78))
+        );
+        let end_coords = calculator.end_coords.unwrap();
+        assert!((end_coords.latitude - 31.768).abs() < 1e-9);
+        assert!((end_coords.longitude - 35.214).abs() < 1e-9);
+
+        calculator.set_coords((1.0, 2.0), Coordinates::new(3.0, 4.0));
+        pretty_assertions::assert_eq!(calculator.start_coords, Some(Coordinates::new(1.0, 2.0)));
+        pretty_assertions::assert_eq!(calculator.end_coords, Some(Coordinates::new(3.0, 4.0)));
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_calculate_route_via() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let first_leg = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 600, 300, 1000))
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("from".into(), "x:1 y:1".into()),
+                mockito::Matcher::UrlEncoded("to".into(), "x:2 y:2".into()),
+                mockito::Matcher::UrlEncoded("at".into(), "0".into()),
+            ]))
+            .create();
+        let second_leg = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 900, 900, 2500))
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("from".into(), "x:2 y:2".into()),
+                mockito::Matcher::UrlEncoded("to".into(), "x:3 y:3".into()),
+                mockito::Matcher::UrlEncoded("at".into(), "10".into()),
+            ]))
+            .create();
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .build();
+        calculator
+            .set_coords((1.0, 1.0), (3.0, 3.0))
+            .set_via_coords([(2.0, 2.0)]);
+
+        let route = calculator.calculate_route_via().unwrap();
+
+        first_leg.assert();
+        second_leg.assert();
+
+        pretty_assertions::assert_eq!(route.legs.len(), 2);
+        pretty_assertions::assert_eq!(route.legs[1].departure_time, route.legs[0].arrival_time);
+        pretty_assertions::assert_eq!(route.duration.as_secs(), 1500);
+        pretty_assertions::assert_eq!(route.traffic_delay.as_secs(), 300);
+        pretty_assertions::assert_eq!(route.distance, 3500);
+        pretty_assertions::assert_eq!(route.arrival_time, route.legs[1].arrival_time);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_address_to_coords_with_cache() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let mock = server
+            .mock("GET", "/il-SearchServer/mozi")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"bounds":null,"city":"Tel Aviv","location":{"lat":32.08,"lon":34.78},"name":"Tel Aviv"}]"#)
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        let cache = Arc::new(GeocodeCache::new(10, std::time::Duration::from_secs(60)));
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_region(Region::IL)
+            .set_base_url(url.as_str())
+            .set_geocode_cache(cache.clone())
+            .build();
+
+        calculator.set_address("Tel Aviv", "tel aviv").unwrap();
+        calculator.set_address("Tel Aviv", "Tel Aviv").unwrap();
+
+        mock.assert();
+        pretty_assertions::assert_eq!(calculator.end_coords, Some(Coordinates::new(32.08, 34.78)));
+        pretty_assertions::assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_calculate_route_with_cache() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let mock = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 600, 300, 1000))
+            .match_query(mockito::Matcher::Any)
+            .expect(2)
+            .create();
+
+        let cache = RouteCache::new(10, std::time::Duration::from_secs(3600))
+            .with_clock(|| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_800_000_000));
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_route_cache(cache)
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        let first = calculator.calculate_route().unwrap();
+        calculator.set_coords((32.080001, 34.78), (31.768, 35.214));
+        let second = calculator.calculate_route().unwrap();
+        pretty_assertions::assert_eq!(first.segments, second.segments);
+
+        let cache = calculator.route_cache().unwrap();
+        pretty_assertions::assert_eq!(cache.hits(), 1);
+        pretty_assertions::assert_eq!(cache.misses(), 1);
+
+        cache.invalidate_route(
+            &Coordinates::new(32.08, 34.78),
+            &Coordinates::new(31.768, 35.214),
+        );
+        calculator.calculate_route().unwrap();
+
+        mock.assert();
+        pretty_assertions::assert_eq!(cache.misses(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_retry_policy() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let failures = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(503)
+            .match_query(mockito::Matcher::Any)
+            .expect(2)
+            .create();
+        let success = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 600, 300, 1000))
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        let policy = RetryPolicy::new(3).with_base_delay(std::time::Duration::from_millis(1));
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_retry_policy(policy.clone())
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        let summary = calculator.calculate_route().unwrap();
+        failures.assert();
+        success.assert();
+        pretty_assertions::assert_eq!(summary.distance, 1000);
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_retry_policy(policy.clone().with_retry_on_server_errors(false))
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+        server.reset();
+        let failure = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(503)
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        assert!(calculator.calculate_route().is_err());
+        failure.assert();
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_retry_policy(policy.clone())
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+        server.reset();
+        let throttled = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .match_query(mockito::Matcher::Any)
+            .expect(2)
+            .create();
+        let success = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 600, 300, 1000))
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        pretty_assertions::assert_eq!(calculator.calculate_route().unwrap().distance, 1000);
+        throttled.assert();
+        success.assert();
+
+        server.reset();
+        let throttled = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(429)
+            .with_header("retry-after", "3600")
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        let error = calculator.calculate_route().unwrap_err();
+        assert!(error.is_retryable());
+        pretty_assertions::assert_eq!(error.status(), Some(429));
+        throttled.assert();
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_retry_policy(policy.with_retry_on_rate_limited(false))
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+        server.reset();
+        let throttled = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(429)
+            .match_query(mockito::Matcher::Any)
+            .expect(1)
+            .create();
+
+        assert!(calculator.calculate_route().is_err());
+        throttled.assert();
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_rate_limiter() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let route = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 600, 300, 1000))
+            .match_query(mockito::Matcher::Any)
+            .expect(3)
+            .create();
+
+        let limiter = RateLimiter::default().with_routing_limit(RateLimit::new(20.0, 1));
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_rate_limiter(limiter.clone())
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            calculator.calculate_route().unwrap();
+        }
+        route.assert();
+        assert!(start.elapsed() >= std::time::Duration::from_millis(90));
+        assert!(limiter.reserve(Endpoint::Routing) > std::time::Duration::ZERO);
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_http_client() {
+        let mut server = mockito::Server::new();
+        let url = server.url() + "/";
+
+        let route = server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .match_header("x-client", "injected")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(route_answer("", 600, 300, 1000))
+            .match_query(mockito::Matcher::Any)
+            .expect(2)
+            .create();
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            "x-client",
+            reqwest::header::HeaderValue::from_static("injected"),
+        );
+        let client = reqwest::blocking::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_blocking_client(client)
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        calculator.calculate_route().unwrap();
+        calculator.calculate_route().unwrap();
+        route.assert();
+
+        server.reset();
+        server
+            .mock("GET", "/row-RoutingManager/routingRequest")
+            .with_status(200)
+            .with_chunked_body(|writer| {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                writer.write_all(b"{}")
+            })
+            .match_query(mockito::Matcher::Any)
+            .create();
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_base_url(url.as_str())
+            .set_connect_timeout(std::time::Duration::from_secs(1))
+            .set_read_timeout(std::time::Duration::from_millis(50))
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        assert!(matches!(
+            calculator.calculate_route(),
+            Err(WazeRouteCalculatorError::NetworkError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_fake_transport() {
+        use crate::transport::FakeTransport;
+
+        let transport = Arc::new(
+            FakeTransport::new()
+                .with_response(
+                    "SearchServer",
+                    TransportResponse::new(
+                        200,
+                        r#"[{"city":"Tel Aviv","location":{"lat":32.08,"lon":34.78}}]"#,
+                    ),
+                )
+                .with_response("routingRequest", TransportResponse::new(503, ""))
+                .with_response(
+                    "routingRequest",
+                    TransportResponse::new(200, route_answer("", 600, 300, 1000)),
+                ),
+        );
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_region(Region::IL)
+            .set_retry_policy(RetryPolicy::new(2).with_base_delay(std::time::Duration::ZERO))
+            .set_transport(transport.clone())
+            .build();
+        calculator.set_address("Tel Aviv", "Tel Aviv").unwrap();
+
+        let summary = calculator.calculate_route().unwrap();
+        pretty_assertions::assert_eq!(summary.distance, 1000);
+
+        let endpoints = transport
+            .requests()
+            .iter()
+            .map(|request| request.endpoint)
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(
+            endpoints,
+            vec![
+                Endpoint::Search,
+                Endpoint::Search,
+                Endpoint::Routing,
+                Endpoint::Routing
+            ]
+        );
+    }
+
+    fn create_mock_waze_result() -> WazeResult {
+        WazeResult {
+            path: Some(WazePath {
+                segment_id: 0,
+                node_id: 0,
+                x: 0.0,
+                y: 0.0,
+                direction: false,
+            }),
+            cross_time: 120,
+            cross_time_without_real_time: 100,
+            length: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_add_up_route() {
+        let calculator = WazeRouteCalculator::builder()
+            .set_region(Region::US)
+            .set_vehicle_type(VehicleType::CAR)
+            .build();
+
+        let results = vec![create_mock_waze_result()];
+
+        let (route_time, route_distance) = calculator.add_up_route(&results, true, false);
+        pretty_assertions::assert_eq!(route_time, 2.0); // 120 seconds / 60 = 2 minutes
+        pretty_assertions::assert_eq!(route_distance, 1.0); // 1000 meters / 1000 = 1 kilometer
 
         let (route_time, route_distance) = calculator.add_up_route(&results, false, false);
         pretty_assertions::assert_eq!(route_time, 1.6666666666666667); // 100 seconds / 60 = 1.6667 minutes
         pretty_assertions::assert_eq!(route_distance, 1.0); // 1000 meters / 1000 = 1 kilometer
     }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_route_summary() {
+        use crate::transport::FakeTransport;
+
+        let transport = FakeTransport::new().with_response(
+            "routingRequest",
+            TransportResponse::new(200, r#"{"response":{"routeName":"Ayalon","results":[{"path":{"segmentId":1,"nodeId":2,"x":34.78,"y":32.08,"direction":true},"length":1234,"crossTime":95,"crossTimeWithoutRealTime":70},{"path":null,"length":66,"crossTime":30,"crossTimeWithoutRealTime":25}]},"coords":[{"x":34.78,"y":32.08,"z":"NaN"},{"x":34.79,"y":32.09,"z":"NaN"}]}"#),
+        );
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_transport(transport)
+            .set_departure_time(DepartureTime::InMinutes(30))
+            .build();
+        calculator.set_coords((32.08, 34.78), (32.09, 34.79));
+
+        let half_hour = Duration::from_secs(1800);
+        let before = SystemTime::now();
+        let summary = calculator.calculate_route().unwrap();
+        let after = SystemTime::now();
+
+        pretty_assertions::assert_eq!(summary.route_name, "Ayalon");
+        pretty_assertions::assert_eq!(summary.duration, Duration::from_secs(125));
+        pretty_assertions::assert_eq!(summary.free_flow_duration, Duration::from_secs(95));
+        pretty_assertions::assert_eq!(summary.traffic_delay, Duration::from_secs(30));
+        pretty_assertions::assert_eq!(summary.distance, 1300);
+        pretty_assertions::assert_eq!(summary.distance_km(), 1.3);
+        assert!(summary.departure_time >= before + half_hour);
+        assert!(summary.departure_time <= after + half_hour);
+        pretty_assertions::assert_eq!(
+            summary.arrival_time,
+            summary.departure_time + Duration::from_secs(125)
+        );
+        pretty_assertions::assert_eq!(summary.segments.len(), 2);
+        pretty_assertions::assert_eq!(summary.geometry.len(), 2);
+
+        let json = serde_json::to_value(&summary).unwrap();
+        pretty_assertions::assert_eq!(json["duration"]["secs"], 125);
+        pretty_assertions::assert_eq!(json["traffic_delay"]["secs"], 30);
+        let round_trip: RouteSummary = serde_json::from_value(json).unwrap();
+        pretty_assertions::assert_eq!(round_trip, summary);
+    }
+
+    #[test]
+    fn test_calculate_route_alternatives() {
+        let answer: Value = serde_json::from_str(r#"{"alternatives":[{"response":{"routeName":"Route 1","shortRouteName":"R1","routeType":["FASTEST"],"results":[{"path":null,"length":1000,"crossTime":120,"crossTimeWithoutRealTime":60}]},"coords":[{"x":34.78,"y":32.08,"z":"NaN"},{"x":34.8,"y":32.1,"z":"NaN"}]},{"response":{"shortRouteName":"Route 6","results":[{"path":null,"length":2000,"crossTime":180,"crossTimeWithoutRealTime":180},{"path":null,"length":500,"crossTime":60,"crossTimeWithoutRealTime":60}]}}]}"#).unwrap();
+
+        let calculator = WazeRouteCalculator::builder().build();
+
+        let routes = WazeRouteCalculator::parse_route_answer(&answer).unwrap();
+        let now = SystemTime::now();
+        let alternatives = calculator.summarize_routes(routes, now);
+
+        pretty_assertions::assert_eq!(alternatives.len(), 2);
+        pretty_assertions::assert_eq!(alternatives[0].route_name, "Route 1");
+        pretty_assertions::assert_eq!(alternatives[0].duration.as_secs(), 120);
+        pretty_assertions::assert_eq!(alternatives[0].free_flow_duration.as_secs(), 60);
+        pretty_assertions::assert_eq!(alternatives[0].traffic_delay.as_secs(), 60);
+        pretty_assertions::assert_eq!(alternatives[0].geometry.len(), 2);
+        pretty_assertions::assert_eq!(alternatives[0].geometry[1], WazeCoord { x: 34.8, y: 32.1 });
+        pretty_assertions::assert_eq!(alternatives[0].distance, 1000);
+        pretty_assertions::assert_eq!(alternatives[1].route_name, "Route 6");
+        pretty_assertions::assert_eq!(alternatives[1].duration.as_secs(), 240);
+        pretty_assertions::assert_eq!(alternatives[1].traffic_delay.as_secs(), 0);
+        pretty_assertions::assert_eq!(alternatives[1].distance, 2500);
+        pretty_assertions::assert_eq!(alternatives[1].distance_km(), 2.5);
+
+        let json = serde_json::to_string(&alternatives[1]).unwrap();
+        let summary: RouteSummary = serde_json::from_str(&json).unwrap();
+        pretty_assertions::assert_eq!(summary, alternatives[1]);
+        pretty_assertions::assert_eq!(alternatives[1].segments.len(), 2);
+        pretty_assertions::assert_eq!(alternatives[1].departure_time, now);
+        pretty_assertions::assert_eq!(
+            alternatives[1].arrival_time,
+            now + std::time::Duration::from_secs(240)
+        );
+
+        let answer: Value = serde_json::from_str(r#"{"response":{"results":[{"path":{"segmentId":1,"nodeId":2,"x":34.78,"y":32.08,"direction":true},"length":1000,"crossTime":120,"crossTimeWithoutRealTime":60}]},"coords":[{"x":34.78,"y":32.08,"z":"NaN"},{"x":34.79,"y":32.09,"z":"NaN"},{"x":34.8,"y":32.1,"z":"NaN"}]}"#).unwrap();
+        let routes = WazeRouteCalculator::parse_route_answer(&answer).unwrap();
+        let route = calculator.summarize_best_route(routes, now).unwrap();
+        pretty_assertions::assert_eq!(route.geometry.len(), 3);
+        pretty_assertions::assert_eq!(route.geometry[2], WazeCoord { x: 34.8, y: 32.1 });
+    }
+
+    #[test]
+    fn test_departure_time_offset() {
+        let now = SystemTime::now();
+        let hour = std::time::Duration::from_secs(3600);
+
+        pretty_assertions::assert_eq!(DepartureTime::Now.to_minutes_offset(now), 0);
+        pretty_assertions::assert_eq!(DepartureTime::InMinutes(-15).to_minutes_offset(now), -15);
+        pretty_assertions::assert_eq!(DepartureTime::At(now + hour).to_minutes_offset(now), 60);
+        pretty_assertions::assert_eq!(DepartureTime::At(now - hour).to_minutes_offset(now), -60);
+        pretty_assertions::assert_eq!(
+            DepartureTime::InMinutes(60).to_system_time(now).unwrap(),
+            now + hour
+        );
+        pretty_assertions::assert_eq!(
+            DepartureTime::InMinutes(-60).to_system_time(now).unwrap(),
+            now - hour
+        );
+        for minutes in [i64::MAX, i64::MIN, i64::MAX / 60] {
+            assert!(matches!(
+                DepartureTime::InMinutes(minutes).to_system_time(now),
+                Err(WazeRouteCalculatorError::InvalidDepartureTime(_))
+            ));
+        }
+
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_departure_time(now + hour)
+            .build();
+        calculator.start_coords = Some(Coordinates::default());
+        calculator.end_coords = Some(Coordinates::default());
+
+        let request = calculator.route_request(
+            &RouteRequest::new(Coordinates::default(), Coordinates::default()),
+            calculator.departure_time.to_minutes_offset(now),
+            1,
+        );
+        let at = ("at".to_string(), "60".to_string());
+        pretty_assertions::assert_eq!(request.query.contains(&at), true);
+    }
+
+    /// Returns every copy of `value` with one node replaced by an unexpected value or removed.
+    fn mutations(value: &Value) -> Vec<Value> {
+        let mut result = vec![
+            serde_json::json!(null),
+            serde_json::json!(true),
+            serde_json::json!(-1),
+            serde_json::json!(i64::MAX),
+            serde_json::json!(1e300),
+            serde_json::json!("x"),
+            serde_json::json!([]),
+            serde_json::json!({}),
+        ];
+
+        match value {
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let mut removed = items.clone();
+                    removed.remove(index);
+                    result.push(Value::Array(removed));
+
+                    for mutation in mutations(item) {
+                        let mut copy = items.clone();
+                        copy[index] = mutation;
+                        result.push(Value::Array(copy));
+                    }
+                }
+            }
+            Value::Object(fields) => {
+                for (key, field) in fields {
+                    let mut removed = fields.clone();
+                    removed.remove(key);
+                    result.push(Value::Object(removed));
+
+                    for mutation in mutations(field) {
+                        let mut copy = fields.clone();
+                        copy.insert(key.clone(), mutation);
+                        result.push(Value::Object(copy));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        result
+    }
+
+    #[test]
+    fn test_parsers_never_panic() {
+        let corpus = [
+            r#"[{"bounds":{"top":1.0,"bottom":2.0,"left":4.0,"right":3.0},"city":"Tel Aviv","location":{"lat":32.08,"lon":34.78},"name":"Tel Aviv","segmentId":1,"streetId":2}]"#,
+            r#"{"response":{"results":[{"path":{"segmentId":1,"nodeId":2,"x":34.78,"y":32.08,"direction":true},"length":1500,"crossTime":120,"crossTimeWithoutRealTime":90,"street":0,"instruction":{"opcode":"ROUNDABOUT_EXIT","arg":2,"name":"Ayalon"}},{"path":null,"length":500,"crossTime":60,"crossTimeWithoutRealTime":60,"street":1}],"routeName":"Ayalon","streetNames":["Ayalon",null]},"coords":[{"x":34.78,"y":32.08}]}"#,
+            r#"{"alternatives":[{"response":{"results":[{"path":null,"length":1,"crossTime":2,"crossTimeWithoutRealTime":3}]}}]}"#,
+            r#"{"response":[{"results":[]}]}"#,
+            r#"{"error":"Internal Error"}"#,
+        ];
+        let calculator = WazeRouteCalculator::builder().build();
+        let coords = Coordinates::new(32.08, 34.78);
+        let mut checked = 0;
+
+        for sample in corpus {
+            let sample: Value = serde_json::from_str(sample).unwrap();
+
+            for answer in std::iter::once(sample.clone()).chain(mutations(&sample)) {
+                let _ = WazeRouteCalculator::parse_coords_answer(answer.clone(), "Tel Aviv");
+                let _ = WazeRouteCalculator::parse_reverse_answer(answer.clone(), &coords);
+
+                if let Ok(routes) = WazeRouteCalculator::parse_route_answer(&answer) {
+                    let summaries = calculator.summarize_routes(routes, SystemTime::now());
+                    for summary in &summaries {
+                        let _ = summary.directions();
+                        let _ = summary.to_gpx();
+                    }
+                    let _ = MultiLegRoute::from(summaries);
+                }
+                checked += 1;
+            }
+        }
+        assert!(checked > 500);
+
+        for body in ["", "null", "<html>captcha</html>", "[1,2", "{\"response\":"] {
+            assert!(crate::transport::TransportResponse::new(200, body)
+                .json()
+                .and_then(|answer| WazeRouteCalculator::parse_route_answer(&answer))
+                .is_err());
+        }
+
+        // A small linear congruential generator, to keep the corpus deterministic.
+        let mut seed: u64 = 42;
+        let alphabet: Vec<char> = "0123456789.,-+ NSEWnsew°'\"′″x".chars().collect();
+        for _ in 0..5000 {
+            let text: String = (0..(seed % 24))
+                .map(|_| {
+                    seed = seed
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    alphabet[(seed >> 33) as usize % alphabet.len()]
+                })
+                .collect();
+            let _ = parse_coordinates(&text);
+            seed = seed.wrapping_add(1);
+        }
+    }
+
+    #[test]
+    fn test_missing_coordinates() {
+        let calculator = WazeRouteCalculator::builder().build();
+
+        assert!(matches!(
+            calculator.legs(),
+            Err(WazeRouteCalculatorError::MissingCoordinates("start"))
+        ));
+        assert!(matches!(
+            WazeRouteCalculator::parse_route_answer(&serde_json::json!({"alternatives": 1})),
+            Err(WazeRouteCalculatorError::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            WazeRouteCalculator::parse_route_answer(&serde_json::json!({"error": {"code": 1}})),
+            Err(WazeRouteCalculatorError::WazeApiError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_error_details() {
+        use crate::transport::FakeTransport;
+
+        let transport = FakeTransport::new()
+            .with_response("SearchServer", TransportResponse::new(200, "[]"))
+            .with_response(
+                "routingRequest",
+                TransportResponse::new(429, "x".repeat(1000)),
+            )
+            .with_response(
+                "routingRequest",
+                TransportResponse::new(200, "<html><body>captcha</body></html>"),
+            )
+            .with_response("routingRequest", TransportResponse::new(403, "Forbidden"));
+        let mut calculator = WazeRouteCalculator::builder()
+            .set_region(Region::IL)
+            .set_transport(transport)
+            .build();
+        calculator.set_coords((32.08, 34.78), (31.768, 35.214));
+
+        let error = calculator.address_to_coords("Nowhere").unwrap_err();
+        assert!(
+            matches!(&error, WazeRouteCalculatorError::NoResults(address) if address == "Nowhere")
+        );
+
+        let error = calculator.calculate_route().unwrap_err();
+        let WazeRouteCalculatorError::HttpStatus {
+            status,
+            url,
+            query,
+            body,
+        } = &error
+        else {
+            panic!("unexpected error: {:?}", error);
+        };
+        pretty_assertions::assert_eq!(*status, 429);
+        assert!(url.ends_with("il-RoutingManager/routingRequest"));
+        assert!(query.contains("nPaths=1"));
+        pretty_assertions::assert_eq!(body.len(), WazeRouteCalculatorError::MAX_BODY_LENGTH + 3);
+        assert!(error.is_retryable() && error.is_rate_limited());
+
+        let error = calculator.calculate_route().unwrap_err();
+        assert!(matches!(
+            error,
+            WazeRouteCalculatorError::HtmlResponse { status: 200, .. }
+        ));
+        assert!(!error.is_retryable() && error.is_rate_limited());
+
+        let error = calculator.calculate_route().unwrap_err();
+        pretty_assertions::assert_eq!(error.status(), Some(403));
+        assert!(!error.is_retryable() && !error.is_rate_limited());
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_route_request() {
+        use crate::transport::FakeTransport;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<WazeRouteCalculator>();
+
+        let transport = Arc::new(FakeTransport::new().with_response(
+            "routingRequest",
+            TransportResponse::new(200, route_answer("", 600, 300, 1000)),
+        ));
+        let calculator = Arc::new(
+            WazeRouteCalculator::builder()
+                .set_avoid_toll_roads(true)
+                .set_transport(transport.clone())
+                .build(),
+        );
+
+        let handles = (0..4)
+            .map(|index| {
+                let calculator = calculator.clone();
+                std::thread::spawn(move || {
+                    let mut request = RouteRequest::new((32.08, 34.78), (31.768, 35.214));
+                    if index == 0 {
+                        request = request
+                            .with_avoid_toll_roads(false)
+                            .with_vehicle_type(VehicleType::TAXI)
+                            .with_departure_time(DepartureTime::InMinutes(30));
+                    }
+                    calculator.route(&request).unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            pretty_assertions::assert_eq!(handle.join().unwrap().distance, 1000);
+        }
+
+        let queries = transport
+            .requests()
+            .iter()
+            .map(|request| request.query_string())
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(queries.len(), 4);
+        let overridden = queries
+            .iter()
+            .filter(|query| {
+                query.contains("AVOID_TOLL_ROADS:f")
+                    && query.contains("vehicleType=TAXI")
+                    && query.contains("at=30")
+            })
+            .count();
+        pretty_assertions::assert_eq!(overridden, 1);
+        pretty_assertions::assert_eq!(
+            queries
+                .iter()
+                .filter(|query| query.contains("AVOID_TOLL_ROADS:t"))
+                .count(),
+            3
+        );
+        assert!(calculator.start_coords.is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "blocking")]
+    fn test_route_options() {
+        use crate::transport::FakeTransport;
+
+        let transport = Arc::new(FakeTransport::new().with_response(
+            "routingRequest",
+            TransportResponse::new(200, route_answer("", 600, 300, 1000)),
+        ));
+        let calculator = WazeRouteCalculator::builder()
+            .set_route_options(RouteOptions::default().with_avoid_highways(true))
+            .set_avoid_ferries(true)
+            .set_transport(transport.clone())
+            .build();
+
+        let request = RouteRequest::new((32.08, 34.78), (31.768, 35.214));
+        calculator.route(&request).unwrap();
+        calculator
+            .route(
+                &request
+                    .with_options(RouteOptions::empty().with_allow_u_turns(false))
+                    .with_avoid_toll_roads(true),
+            )
+            .unwrap();
+
+        let options = transport
+            .requests()
+            .iter()
+            .map(|request| {
+                request
+                    .query
+                    .iter()
+                    .find(|(name, _)| name == "options")
+                    .unwrap()
+                    .1
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(
+            options,
+            vec![
+                "AVOID_FERRIES:t,AVOID_PRIMARIES:t,AVOID_TOLL_ROADS:f,AVOID_TRAILS:t".to_string(),
+                "ALLOW_UTURNS:f,AVOID_TOLL_ROADS:t".to_string(),
+            ]
+        );
+    }
 }
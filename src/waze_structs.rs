@@ -1,9 +1,11 @@
+use crate::route_instructions::Maneuver;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use serde_json::Value;
+use std::time::{Duration, SystemTime};
 
 /// Struct representing the coordinates of a location.
-#[derive(Copy, Clone, Default, Debug, PartialEq)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Coordinates {
     /// The latitude of the location.
     pub latitude: f64,
@@ -15,6 +17,29 @@ pub struct Coordinates {
     pub bound: Option<Bound>,
 }
 
+impl Coordinates {
+    /// Creates a new `Coordinates` struct without bounds.
+    ///
+    /// # Arguments
+    ///
+    /// * `latitude` - The latitude of the location.
+    /// * `longitude` - The longitude of the location.
+    pub fn new(latitude: f64, longitude: f64) -> Self {
+        Coordinates {
+            latitude,
+            longitude,
+            bound: None,
+        }
+    }
+}
+
+impl From<(f64, f64)> for Coordinates {
+    /// Converts a `(latitude, longitude)` pair to a `Coordinates` struct.
+    fn from((latitude, longitude): (f64, f64)) -> Self {
+        Coordinates::new(latitude, longitude)
+    }
+}
+
 impl From<WazeAddressCoordinates> for Coordinates {
     /// Converts a `WazeAddressCoordinates` struct to a `Coordinates` struct.
     fn from(coord: WazeAddressCoordinates) -> Self {
@@ -26,6 +51,26 @@ impl From<WazeAddressCoordinates> for Coordinates {
     }
 }
 
+impl From<&Coordinates> for WazeAddressCoordinates {
+    /// Converts a `Coordinates` struct to a `WazeAddressCoordinates` struct.
+    fn from(coords: &Coordinates) -> Self {
+        WazeAddressCoordinates {
+            lat: coords.latitude,
+            lon: coords.longitude,
+        }
+    }
+}
+
+impl From<&WazeAddress> for Coordinates {
+    /// Converts a `WazeAddress` struct to a `Coordinates` struct, keeping its bounds.
+    fn from(address: &WazeAddress) -> Self {
+        Coordinates {
+            bound: address.bounds,
+            ..Coordinates::from(address.location)
+        }
+    }
+}
+
 #[derive(Copy, Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Bound {
     pub top: f64,
@@ -34,6 +79,18 @@ pub struct Bound {
     pub right: f64,
 }
 
+impl Bound {
+    /// Returns the bound with `top` above `bottom` and `left` west of `right`.
+    pub fn normalized(&self) -> Self {
+        Bound {
+            top: self.top.max(self.bottom),
+            bottom: self.top.min(self.bottom),
+            left: self.left.min(self.right),
+            right: self.left.max(self.right),
+        }
+    }
+}
+
 /// Type alias for a vector of `WazeAddress` structs.
 pub type WazeAddressAnswer = Vec<WazeAddress>;
 
@@ -52,12 +109,14 @@ pub struct WazeAddress {
     /// The geographical coordinates of the address.
     pub location: WazeAddressCoordinates,
     /// The name of the address.
+    #[serde(default)]
     pub name: String,
     /// The number of the address.
     pub number: Option<String>,
     /// The provider of the address.
     pub provider: Option<String>,
     /// The segment ID of the address.
+    #[serde(default)]
     pub segment_id: i64,
     /// The state of the address, represented as a JSON value.
     pub state: Option<Value>,
@@ -66,6 +125,7 @@ pub struct WazeAddress {
     /// The street of the address.
     pub street: Option<String>,
     /// The street ID of the address.
+    #[serde(default)]
     pub street_id: i64,
 }
 
@@ -82,6 +142,148 @@ pub struct WazeResult {
     pub length: i64,
     pub cross_time: i64,
     pub cross_time_without_real_time: i64,
+    /// The index of the segment street in `WazeRoute::street_names`.
+    #[serde(default)]
+    pub street: Option<usize>,
+    /// The maneuver at the end of the segment, returned when `returnInstructions` is set.
+    #[serde(default)]
+    pub instruction: Option<WazeInstruction>,
+}
+
+/// Represents a maneuver instruction attached to a route segment.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WazeInstruction {
+    /// The maneuver opcode, e.g. `TURN_LEFT` or `ROUNDABOUT_RIGHT`.
+    pub opcode: String,
+    /// The maneuver argument, the exit number for roundabouts.
+    #[serde(default)]
+    pub arg: Option<i64>,
+    /// The instruction text, when Waze provides one.
+    #[serde(default)]
+    pub instruction_text: Option<String>,
+    /// The name of the maneuver target, e.g. an exit sign.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Represents a single route returned by the Waze routing server.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WazeRoute {
+    /// The segments of the route.
+    pub results: Vec<WazeResult>,
+    /// The name of the route, usually the main roads it takes.
+    pub route_name: Option<String>,
+    /// The short name of the route.
+    pub short_route_name: Option<String>,
+    /// The route types, e.g. `FASTEST`.
+    #[serde(default)]
+    pub route_type: Vec<String>,
+    /// The full route geometry, returned when `returnGeometries` is set.
+    ///
+    /// Waze sends it as a `coords` array next to the route `response`, which
+    /// `parse_route_answer` moves here.
+    #[serde(default)]
+    pub coords: Vec<WazeCoord>,
+    /// The street names referenced by the segments.
+    #[serde(default)]
+    pub street_names: Vec<Option<String>>,
+}
+
+/// Struct representing a calculated route between the start and end coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteSummary {
+    /// The name of the route.
+    pub route_name: String,
+
+    /// The route duration with realtime traffic.
+    pub duration: Duration,
+
+    /// The route duration without realtime traffic.
+    pub free_flow_duration: Duration,
+
+    /// The extra time caused by traffic, `duration` minus `free_flow_duration`.
+    pub traffic_delay: Duration,
+
+    /// The route distance in meters.
+    pub distance: u64,
+
+    /// The departure time the route was calculated for.
+    pub departure_time: SystemTime,
+
+    /// The predicted arrival time.
+    pub arrival_time: SystemTime,
+
+    /// The segments of the route.
+    pub segments: Vec<WazeResult>,
+
+    /// The route geometry as an ordered list of points.
+    #[serde(default)]
+    pub geometry: Vec<WazeCoord>,
+
+    /// The turn-by-turn maneuvers of the route.
+    #[serde(default)]
+    pub maneuvers: Vec<Maneuver>,
+}
+
+/// Struct representing a route through via points, with one `RouteSummary` per leg.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiLegRoute {
+    /// The legs of the route, in order.
+    pub legs: Vec<RouteSummary>,
+
+    /// The total duration with realtime traffic.
+    pub duration: Duration,
+
+    /// The total duration without realtime traffic.
+    pub free_flow_duration: Duration,
+
+    /// The total extra time caused by traffic.
+    pub traffic_delay: Duration,
+
+    /// The total distance in meters.
+    pub distance: u64,
+
+    /// The departure time of the first leg.
+    pub departure_time: SystemTime,
+
+    /// The predicted arrival time of the last leg.
+    pub arrival_time: SystemTime,
+}
+
+impl From<Vec<RouteSummary>> for MultiLegRoute {
+    /// Sums up the legs into a `MultiLegRoute`.
+    fn from(legs: Vec<RouteSummary>) -> Self {
+        MultiLegRoute {
+            duration: legs
+                .iter()
+                .fold(Duration::ZERO, |total, x| total.saturating_add(x.duration)),
+            free_flow_duration: legs.iter().fold(Duration::ZERO, |total, x| {
+                total.saturating_add(x.free_flow_duration)
+            }),
+            traffic_delay: legs.iter().fold(Duration::ZERO, |total, x| {
+                total.saturating_add(x.traffic_delay)
+            }),
+            distance: legs
+                .iter()
+                .fold(0, |total: u64, x| total.saturating_add(x.distance)),
+            departure_time: legs
+                .first()
+                .map_or(SystemTime::UNIX_EPOCH, |x| x.departure_time),
+            arrival_time: legs
+                .last()
+                .map_or(SystemTime::UNIX_EPOCH, |x| x.arrival_time),
+            legs,
+        }
+    }
+}
+
+impl RouteSummary {
+    /// Returns the route distance in kilometers.
+    pub fn distance_km(&self) -> f64 {
+        self.distance as f64 / 1000.0
+    }
 }
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
@@ -93,3 +295,10 @@ pub struct WazePath {
     pub y: f64,
     pub direction: bool,
 }
+
+/// A point of the route geometry, `x` being the longitude and `y` the latitude.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WazeCoord {
+    pub x: f64,
+    pub y: f64,
+}